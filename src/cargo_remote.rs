@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::path::Path;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json as json;
@@ -46,6 +49,8 @@ impl Default for CargoRemote {
             repository: Project {
                 root: std::path::PathBuf::new(),
                 ignore_crates: Vec::new(),
+                docsrs: None,
+                rust_analyzer: Default::default(),
             },
         }
     }
@@ -115,4 +120,31 @@ impl CargoRemote {
         let (_, messages) = self.run_cargo_command(&args, backtrace).await?;
         Ok(messages)
     }
+
+    /// Runs an exact cargo invocation, e.g. the one rust-analyzer computed
+    /// for a single test. Returns the output of the test binary, and the
+    /// compiler output as well if it failed.
+    pub async fn run_invocation(
+        &self,
+        program: Option<&str>,
+        args: &[String],
+        cwd: Option<&Path>,
+        environment: &HashMap<String, String>,
+        backtrace: bool,
+    ) -> Result<Vec<String>> {
+        let output = Command::new(program.unwrap_or("cargo"))
+            .current_dir(cwd.unwrap_or(self.repository.root()))
+            .args(args)
+            .envs(environment)
+            .env("RUST_BACKTRACE", if backtrace { "full" } else { "0" })
+            .output()
+            .await?;
+
+        let mut messages = vec![String::from_utf8_lossy(&output.stdout).trim_end().to_string()];
+        if !output.status.success() {
+            messages.push(String::from_utf8_lossy(&output.stderr).trim_end().to_string());
+        }
+        messages.retain(|message| !message.is_empty());
+        Ok(messages)
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -12,7 +12,7 @@ use crate::mcp::McpNotification;
 use crate::ui::ProjectDescription;
 use crate::{
     lsp::RustAnalyzerLsp,
-    project::{Project, TransportType},
+    project::{Project, RustAnalyzerConfig, TransportType},
 };
 use anyhow::Result;
 use flume::Sender;
@@ -34,6 +34,7 @@ impl ContextNotification {
             ContextNotification::Lsp(LspNotification::Indexing { project, .. }) => project.clone(),
             ContextNotification::Lsp(LspNotification::IndexingProgress(progress)) => progress.project.clone(),
             ContextNotification::Lsp(LspNotification::IndexingPauseResume { project, .. }) => project.clone(),
+            ContextNotification::Lsp(LspNotification::ServerHealth(health)) => health.project.clone(),
             ContextNotification::Docs(DocsNotification::Indexing { project, .. }) => {
                 project.clone()
             }
@@ -62,6 +63,9 @@ impl ContextNotification {
                     if *should_pause { "Paused" } else { "Resumed" }
                 )
             }
+            ContextNotification::Lsp(LspNotification::ServerHealth(health)) => {
+                format!("LSP Server: {}", health.status_message())
+            }
             ContextNotification::Docs(DocsNotification::Indexing { is_indexing, .. }) => {
                 format!(
                     "Docs Indexing: {}",
@@ -176,6 +180,33 @@ impl Context {
                             if let Some(project_ctx) = projects.get_mut(project) {
                                 project_ctx.is_indexing_lsp.store(is_indexing, std::sync::atomic::Ordering::Relaxed);
                             }
+                        } else if let LspNotification::IndexingPauseResume { ref project, should_pause } = notification {
+                            if let Err(e) = cloned_notifier.try_send(ContextNotification::Lsp(notification.clone())) {
+                                if matches!(e, flume::TrySendError::Disconnected(_)) {
+                                    tracing::debug!("Channel closed when forwarding LSP pause notification");
+                                    break; // Exit the loop if the channel is disconnected
+                                } else {
+                                    tracing::error!("Failed to send LSP pause notification: {}", e);
+                                }
+                            }
+                            let project_ctx = cloned_projects.read().await.get(project).cloned();
+                            if let Some(project_ctx) = project_ctx {
+                                // Stopping or starting rust-analyzer takes a while, don't block the loop
+                                tokio::spawn(async move {
+                                    if let Err(e) = project_ctx.lsp.set_paused(should_pause).await {
+                                        tracing::error!("Failed to {} indexing: {:?}", if should_pause { "pause" } else { "resume" }, e);
+                                    }
+                                });
+                            }
+                        } else if let LspNotification::ServerHealth(_) = notification
+                            && let Err(e) = cloned_notifier.try_send(ContextNotification::Lsp(notification.clone()))
+                        {
+                            if matches!(e, flume::TrySendError::Disconnected(_)) {
+                                tracing::debug!("Channel closed when forwarding LSP server health");
+                                break; // Exit the loop if the channel is disconnected
+                            } else {
+                                tracing::error!("Failed to send LSP server health: {}", e);
+                            }
                         }
                     }
                     else => {
@@ -244,6 +275,19 @@ impl Context {
             .map(|p| SerProject {
                 root: p.root().to_string_lossy().to_string().replace('\\', "/"),
                 ignore_crates: p.ignore_crates().to_vec(),
+                docsrs: p.docsrs,
+                rust_analyzer_binary: p
+                    .rust_analyzer
+                    .binary
+                    .as_ref()
+                    .map(|binary| binary.to_string_lossy().replace('\\', "/")),
+                rust_analyzer_args: p.rust_analyzer.args.clone(),
+                rust_analyzer_env: p.rust_analyzer.env.clone(),
+                rust_analyzer_auto_install: p.rust_analyzer.auto_install,
+                rust_analyzer_stop_on_pause: p.rust_analyzer.stop_on_pause,
+                rust_analyzer_max_concurrent_requests: p.rust_analyzer.max_concurrent_requests,
+                rust_analyzer_request_timeout_secs: p.rust_analyzer.request_timeout_secs,
+                rust_analyzer: p.rust_analyzer.settings.clone(),
             })
             .collect();
         let config = SerConfig {
@@ -321,6 +365,17 @@ impl Context {
                 // PathBuf automatically handles forward slashes correctly on all platforms
                 root: PathBuf::from(&project.root),
                 ignore_crates: project.ignore_crates,
+                docsrs: project.docsrs,
+                rust_analyzer: RustAnalyzerConfig {
+                    binary: project.rust_analyzer_binary.map(PathBuf::from),
+                    args: project.rust_analyzer_args,
+                    env: project.rust_analyzer_env,
+                    auto_install: project.rust_analyzer_auto_install,
+                    stop_on_pause: project.rust_analyzer_stop_on_pause,
+                    max_concurrent_requests: project.rust_analyzer_max_concurrent_requests,
+                    request_timeout_secs: project.rust_analyzer_request_timeout_secs,
+                    settings: project.rust_analyzer,
+                },
             };
             // Validate project root before adding
             if !project.root().exists() || !project.root().is_dir() {
@@ -581,6 +636,31 @@ struct SerProject {
     // Paths are stored with forward slashes for cross-platform compatibility
     root: String,
     ignore_crates: Vec<String>,
+    /// Generate the docs with `--cfg docsrs` and `RUSTC_BOOTSTRAP=1`, true if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    docsrs: Option<bool>,
+    /// The rust-analyzer binary, `rust-analyzer` from the PATH if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rust_analyzer_binary: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rust_analyzer_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    rust_analyzer_env: BTreeMap<String, String>,
+    /// Run `rustup component add rust-analyzer` if rust-analyzer is missing
+    #[serde(default)]
+    rust_analyzer_auto_install: bool,
+    /// Stop rust-analyzer while indexing is paused
+    #[serde(default)]
+    rust_analyzer_stop_on_pause: bool,
+    /// How many requests are sent to rust-analyzer at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rust_analyzer_max_concurrent_requests: Option<usize>,
+    /// Seconds after which a rust-analyzer request is given up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rust_analyzer_request_timeout_secs: Option<u64>,
+    /// Free-form rust-analyzer settings, e.g. `cargo.features` or `check.command`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rust_analyzer: Option<serde_json::Value>,
 }
 
 async fn project_descriptions(
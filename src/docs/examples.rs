@@ -0,0 +1,122 @@
+use anyhow::Result;
+use ignore::WalkBuilder;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file from the `examples/` directory of a dependency
+#[derive(Debug, Clone)]
+pub struct ExampleFile {
+    /// Path relative to the dependency root, e.g. `examples/chat.rs`
+    pub path: PathBuf,
+    pub content: String,
+    /// How often the file mentions the symbol
+    pub mentions: usize,
+}
+
+/// The Rust code blocks of a Markdown document, i.e. its doc-tests.
+/// Blocks marked as another language (e.g. `text` or `toml`) are skipped.
+pub fn code_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(bool, Vec<&str>)> = None;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        match current.take() {
+            None => {
+                if let Some(info) = trimmed.strip_prefix("```") {
+                    current = Some((is_rust_block(info), Vec::new()));
+                }
+            }
+            Some((is_rust, lines)) if trimmed.starts_with("```") => {
+                if is_rust && lines.iter().any(|line| !line.trim().is_empty()) {
+                    blocks.push(lines.join("\n"));
+                }
+            }
+            Some((is_rust, mut lines)) => {
+                lines.push(line);
+                current = Some((is_rust, lines));
+            }
+        }
+    }
+    blocks
+}
+
+/// Whether the info string of a fenced block describes a doc-test. Rustdoc
+/// treats unmarked blocks as Rust, and attributes like `no_run` keep them Rust.
+fn is_rust_block(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .all(|token| {
+            matches!(
+                token,
+                "rust" | "ignore" | "no_run" | "should_panic" | "compile_fail" | "test_harness"
+            ) || token.starts_with("edition")
+        })
+}
+
+/// The `.rs` files below `examples/` in `package_root`, ranked by how often
+/// they mention `name`. Files that never mention it are dropped unless no
+/// file does, in which case all examples are returned.
+pub fn example_files(package_root: &Path, name: Option<&str>) -> Result<Vec<ExampleFile>> {
+    let examples_dir = package_root.join("examples");
+    if !examples_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mention = name
+        .map(|name| Regex::new(&format!(r"\b{}\b", regex::escape(name))))
+        .transpose()?;
+
+    let mut files = Vec::new();
+    for entry in WalkBuilder::new(&examples_dir).build() {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        let content = fs::read_to_string(path)?;
+        let mentions = mention
+            .as_ref()
+            .map(|re| re.find_iter(&content).count())
+            .unwrap_or_default();
+        files.push(ExampleFile {
+            path: path.strip_prefix(package_root).unwrap_or(path).to_path_buf(),
+            content,
+            mentions,
+        });
+    }
+
+    if files.iter().any(|file| file.mentions > 0) {
+        files.retain(|file| file.mentions > 0);
+    }
+    files.sort_by(|a, b| b.mentions.cmp(&a.mentions).then_with(|| a.path.cmp(&b.path)));
+    Ok(files)
+}
+
+/// The plain name to search for in example files, e.g. `lock` for
+/// `tokio::sync::Mutex::lock` or `select` for `tokio::select!`
+pub fn mention_name(symbol: &str) -> &str {
+    let symbol = symbol.split_whitespace().last().unwrap_or(symbol);
+    let symbol = symbol.rsplit('@').next().unwrap_or(symbol);
+    let symbol = symbol.rsplit("::").next().unwrap_or(symbol);
+    symbol.trim_end_matches('!')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks() {
+        let markdown = "Some text\n```\nlet a = 1;\n```\n\n```text\noutput\n```\n```rust,no_run\nlet b = 2;\n```\n```toml\n[dependencies]\n```";
+        assert_eq!(code_blocks(markdown), vec!["let a = 1;", "let b = 2;"]);
+    }
+
+    #[test]
+    fn test_mention_name() {
+        assert_eq!(mention_name("tokio::sync::Mutex::lock"), "lock");
+        assert_eq!(mention_name("tokio::select!"), "select");
+        assert_eq!(mention_name("derive@serde::Serialize"), "Serialize");
+        assert_eq!(mention_name("struct Mutex"), "Mutex");
+        assert_eq!(mention_name("Mutex"), "Mutex");
+    }
+}
@@ -1,6 +1,8 @@
 use regex::Regex;
 use serde_json::Value;
 
+use super::walk::SourceLocation;
+
 pub fn extract_md(html: &str) -> String {
     // Remove head section before processing
     let re = regex::Regex::new(r"<head>.*?</head>").unwrap();
@@ -12,6 +14,76 @@ pub fn extract_md(html: &str) -> String {
     remove_backslashes(&remove_tags(&remove_markdown_links(&md)))
 }
 
+/// Extracts the `#[doc(cfg(...))]` annotation of the documented item, e.g.
+/// "crate feature `sync`" from rustdoc's "Available on crate feature sync only"
+/// banner. Only the banner of the item itself is considered, not the ones of
+/// its methods or of the items listed on a module page.
+pub fn extract_feature_gate(html: &str) -> Option<String> {
+    let item_end = [r#"class="docblock"#, r#"<h2 id=""#, r#"class="item-table"#]
+        .iter()
+        .filter_map(|marker| html.find(marker))
+        .min()
+        .unwrap_or(html.len());
+    let header = &html[..item_end];
+    let start = header.find(r#"class="stab portability""#)?;
+    let banner = &header[start..];
+    let banner = &banner[banner.find('>')? + 1..];
+    let banner = &banner[..banner.find("</div>").or_else(|| banner.find("</span>"))?];
+
+    let code = Regex::new(r"<code>([^<]*)</code>").unwrap();
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    let banner = code.replace_all(banner, "`$1`");
+    let banner = tags.replace_all(&banner, "");
+    let banner = banner.split_whitespace().collect::<Vec<_>>().join(" ");
+    let banner = banner
+        .trim_start_matches("Available on ")
+        .trim_end_matches('.')
+        .trim_end_matches(" only");
+    (!banner.is_empty()).then(|| banner.to_string())
+}
+
+/// Extracts where the documented item is defined from rustdoc's "Source" link,
+/// e.g. `../src/tokio/sync/mutex.rs.html#118-125`. The file is relative to the
+/// directory of the crate root (usually `src/`).
+pub fn extract_source_location(html: &str) -> Option<SourceLocation> {
+    let item_end = [r#"class="docblock"#, r#"<h2 id=""#, r#"class="item-table"#]
+        .iter()
+        .filter_map(|marker| html.find(marker))
+        .min()
+        .unwrap_or(html.len());
+    // `class="src"`, or `class="srclink rightside"` in older rustdoc versions
+    let tag = Regex::new(r#"<a [^>]*class="(?:[^"]*\s)?src(?:link)?(?:\s[^"]*)?"[^>]*>"#).unwrap();
+    let href = Regex::new(r#"href="([^"]+)""#).unwrap();
+    let tag = tag.find(&html[..item_end])?;
+    let href = href.captures(tag.as_str())?.get(1)?.as_str();
+    parse_source_href(href)
+}
+
+fn parse_source_href(href: &str) -> Option<SourceLocation> {
+    let (path, lines) = href.split_once('#').unwrap_or((href, ""));
+    let path = path.strip_suffix(".html")?;
+    let mut segments = path
+        .split('/')
+        .skip_while(|segment| *segment == ".." || *segment == ".");
+    // `src/<crate>/<file>`
+    if segments.next()? != "src" {
+        return None;
+    }
+    segments.next()?;
+    let file = segments.collect::<Vec<_>>().join("/");
+    if !file.ends_with(".rs") {
+        return None;
+    }
+    let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+    let start_line = start.parse().unwrap_or(1);
+    let end_line = end.parse().unwrap_or(start_line);
+    Some(SourceLocation {
+        file,
+        start_line,
+        end_line,
+    })
+}
+
 fn remove_markdown_links(input: &str) -> String {
     let re = regex::Regex::new(r"\[([^\[\]]+)\]\(([^)]+)\)").unwrap();
     let replaced = re.replace_all(input, |caps: &regex::Captures| {
@@ -101,3 +173,90 @@ fn extract_lines_after_package(input: &str) -> String {
     }
     resulting_lines.join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = r#"<div class="main-heading"><h1>Struct <a href="index.html">tokio</a>::<wbr><a href="index.html">sync</a>::<wbr><span class="struct">Mutex</span></h1><span class="out-of-band"><a class="src" href="../../src/tokio/sync/mutex.rs.html#118-125">Source</a></span></div>"#;
+
+    fn page(banner: &str, body: &str) -> String {
+        format!(
+            r#"{HEADER}<span class="item-info">{banner}</span><details class="toggle top-doc" open><summary class="hideme"><span>Expand description</span></summary><div class="docblock"><p>An asynchronous Mutex</p></div></details>{body}"#
+        )
+    }
+
+    #[test]
+    fn test_feature_gate() {
+        let html = page(
+            r#"<div class="stab portability">Available on <strong>crate feature <code>sync</code></strong> only.</div>"#,
+            "",
+        );
+        assert_eq!(
+            extract_feature_gate(&html).as_deref(),
+            Some("crate feature `sync`")
+        );
+    }
+
+    #[test]
+    fn test_cfg_gate() {
+        let html = page(
+            r#"<div class="stab portability">Available on <strong>Unix and <code>tokio_unstable</code></strong> only.</div>"#,
+            "",
+        );
+        assert_eq!(
+            extract_feature_gate(&html).as_deref(),
+            Some("Unix and `tokio_unstable`")
+        );
+    }
+
+    #[test]
+    fn test_method_gates_are_ignored() {
+        let method = r##"<h2 id="implementations" class="section-header">Implementations</h2><details class="toggle method-toggle" open><summary><section id="method.blocking_lock" class="method"><h4 class="code-header">pub fn <a href="#method.blocking_lock" class="fn">blocking_lock</a>(&amp;self)</h4></section></summary><span class="item-info"><div class="stab portability">Available on <strong>crate feature <code>sync</code></strong> only.</div></span></details>"##;
+        assert_eq!(extract_feature_gate(&page("", method)), None);
+
+        let module = r#"<h2 id="structs" class="section-header">Structs</h2><dl class="item-table"><dt><a class="struct" href="struct.Mutex.html">Mutex</a><wbr><span class="stab portability" title="Available on crate feature `sync` only"><code>sync</code></span></dt></dl>"#;
+        assert_eq!(extract_feature_gate(module), None);
+    }
+
+    fn location(file: &str, start_line: u32, end_line: u32) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            file: file.to_string(),
+            start_line,
+            end_line,
+        })
+    }
+
+    #[test]
+    fn test_source_location() {
+        assert_eq!(
+            extract_source_location(&page("", "")),
+            location("sync/mutex.rs", 118, 125)
+        );
+
+        let html = r#"<h1>Function <a href="index.html">anyhow</a>::<wbr><span class="fn">Ok</span></h1><a class="srclink rightside" href="../src/anyhow/lib.rs.html#701">source</a><div class="docblock"><p>Equivalent to Ok::&lt;_, anyhow::Error&gt;(value).</p></div>"#;
+        assert_eq!(extract_source_location(html), location("lib.rs", 701, 701));
+
+        let html = r#"<h1>Trait <span class="trait">Context</span></h1><div class="docblock"><a class="src" href="../src/anyhow/context.rs.html#1-10">Source</a></div>"#;
+        assert_eq!(extract_source_location(html), None);
+    }
+
+    #[test]
+    fn test_parse_source_href() {
+        assert_eq!(
+            parse_source_href("../src/tokio/sync/mutex.rs.html#118-125"),
+            location("sync/mutex.rs", 118, 125)
+        );
+        assert_eq!(
+            parse_source_href("../../src/tokio/lib.rs.html#42"),
+            location("lib.rs", 42, 42)
+        );
+        assert_eq!(
+            parse_source_href("../src/tokio/sync/mod.rs.html"),
+            location("sync/mod.rs", 1, 1)
+        );
+        assert_eq!(parse_source_href("../tokio/sync/struct.Mutex.html"), None);
+        assert_eq!(parse_source_href("https://docs.rs/tokio/latest/tokio/"), None);
+        assert_eq!(parse_source_href("../src/tokio/index.html"), None);
+    }
+}
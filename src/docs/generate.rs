@@ -1,17 +1,74 @@
 use crate::project::Project;
 use anyhow::Result;
-use std::process::Command;
+use std::process::{Command, Output};
 
-pub fn generate_docs(project: &Project) -> Result<()> {
-    // Run cargo doc with custom output directory
-    let output = Command::new("cargo")
-        .current_dir(project.root())
-        .args(["doc", "--target-dir", project.cache_folder()])
-        .output()?;
+use super::metadata::CargoMetadata;
 
+/// Generates the docs of the project dependencies into the docs cache.
+///
+/// A bare `cargo doc` only documents the normal dependencies of the default
+/// workspace members. With the metadata, every workspace member and every
+/// direct dependency (including dev-dependencies) is selected explicitly, so
+/// all of them get documented with the features cargo resolved for the project.
+///
+/// Unless turned off with [`Project::docsrs`], the docs are built with
+/// `--cfg docsrs` first, like on docs.rs, so that rustdoc renders the
+/// `#[doc(cfg(...))]` feature gates of the items. If that build fails, the
+/// docs are built a second time without it.
+pub fn generate_docs(project: &Project, metadata: Option<&CargoMetadata>) -> Result<()> {
+    let mut args = vec![
+        "doc".to_string(),
+        "--target-dir".to_string(),
+        project.cache_folder().to_string(),
+    ];
+    if let Some(metadata) = metadata {
+        let members = metadata.workspace_packages();
+        let dependencies = metadata.direct_dependencies();
+        let packages = members
+            .iter()
+            .map(|p| (&p.name, &p.version))
+            .chain(dependencies.iter().map(|d| (&d.name, &d.version)))
+            .filter(|(name, _)| !project.ignore_crates().contains(name));
+        for (name, version) in packages {
+            args.push("-p".to_string());
+            args.push(format!("{name}@{version}"));
+        }
+    }
+
+    if project.docsrs() {
+        let output = run_cargo_doc(project, &args, true)?;
+        if output.status.success() {
+            return Ok(());
+        }
+        tracing::warn!(
+            "Generating docs with `--cfg docsrs` failed, retrying without feature annotations: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let output = run_cargo_doc(project, &args, false)?;
     if !output.status.success() {
-        return Err(anyhow::anyhow!("Failed to generate documentation"));
+        return Err(anyhow::anyhow!(
+            "Failed to generate documentation: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
     }
 
     Ok(())
 }
+
+fn run_cargo_doc(project: &Project, args: &[String], docsrs: bool) -> Result<Output> {
+    let mut command = Command::new("cargo");
+    command.current_dir(project.root()).args(args);
+    if docsrs {
+        // `doc(cfg)` is unstable, most crates only enable it under `docsrs`
+        let flags = match std::env::var("RUSTDOCFLAGS") {
+            Ok(flags) if !flags.trim().is_empty() => format!("{flags} --cfg docsrs"),
+            _ => "--cfg docsrs".to_string(),
+        };
+        command
+            .env("RUSTDOCFLAGS", flags)
+            .env("RUSTC_BOOTSTRAP", "1");
+    }
+    Ok(command.output()?)
+}
@@ -1,5 +1,10 @@
-use super::{utils::get_cargo_dependencies, walk::DocsCache};
+use super::{
+    utils::{ItemKind, get_cargo_dependencies},
+    walk::{DOCS_CACHE_VERSION, DocsCache, DocsItem, SourceLocation},
+};
 use anyhow::Result;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
 #[derive(Debug)]
@@ -58,8 +63,19 @@ impl DocsIndex {
             // Read existing cache file
             match fs::read_to_string(&cache_path) {
                 Ok(cache_content) => {
-                    match serde_json::from_str(&cache_content) {
-                        Ok(parsed_cache) => parsed_cache,
+                    match serde_json::from_str::<DocsCache>(&cache_content) {
+                        Ok(parsed_cache) if parsed_cache.format_version == DOCS_CACHE_VERSION => {
+                            parsed_cache
+                        }
+                        Ok(_) => {
+                            tracing::warn!("Docs cache has an outdated format. Please update the docs cache");
+                            DocsCache::default()
+                        }
+                        // Caches written before the format was versioned don't parse anymore
+                        Err(_) if !cache_content.contains("\"format_version\"") => {
+                            tracing::warn!("Docs cache has an outdated format. Please update the docs cache");
+                            DocsCache::default()
+                        }
                         Err(e) => {
                             tracing::error!("Failed to parse cache file as JSON: {}", e);
                             return Err(anyhow::anyhow!("Failed to parse cache file: {}", e));
@@ -92,42 +108,519 @@ impl DocsIndex {
         &self.dependencies
     }
 
-    pub fn symbols(&self, dependency: &str) -> Option<Vec<String>> {
+    /// Looks up the docs for a symbol path like `tokio::sync::Mutex`,
+    /// `sync::Mutex` or just `Mutex`. Re-exports are followed. For methods and
+    /// other associated items (`Mutex::lock`) the matching section of the
+    /// parent type's page is returned. Returns all matches if the path is
+    /// ambiguous. The kind can be given as a filter, or in the symbol as
+    /// `trait Serialize` or `derive@Serialize`.
+    pub fn lookup(
+        &self,
+        dependency: &str,
+        symbol: &str,
+        kind: Option<ItemKind>,
+    ) -> Vec<(String, String)> {
+        let Some(dep_docs) = self.cache.deps.get(dependency) else {
+            return Vec::new();
+        };
+        let (symbol_kind, symbol) = parse_symbol_query(symbol);
+        let kind = kind.or(symbol_kind);
+        let matches_kind = |item: &DocsItem| kind.is_none_or(|kind| item.kind == kind);
+        let full_path = if symbol == dependency || symbol.starts_with(&format!("{dependency}::")) {
+            symbol.clone()
+        } else {
+            format!("{dependency}::{symbol}")
+        };
+
+        // Exact path, either as written, as a macro or as a derive / attribute macro
+        let exact = [
+            full_path.clone(),
+            format!("{full_path}!"),
+            format!("derive@{full_path}"),
+            format!("attr@{full_path}"),
+        ];
+        for path in exact {
+            if let Some((path, item)) = self.resolve(&path)
+                && matches_kind(item)
+            {
+                return vec![(path, annotated_markdown(item))];
+            }
+        }
+
+        // Associated item of a type, e.g. `Mutex::lock`
+        if let Some((parent, member)) = full_path.rsplit_once("::") {
+            let parents = match self.resolve(parent) {
+                Some((path, item)) => vec![(path, item)],
+                None => self.find_by_suffix(dependency, dep_docs, parent_suffix(dependency, parent)),
+            };
+            let members = parents
+                .into_iter()
+                .filter(|(_, item)| item.kind != ItemKind::Module)
+                .filter_map(|(path, item)| {
+                    let section = member_section(&item.markdown, member)?;
+                    Some((format!("{path}::{member}"), section))
+                })
+                .collect::<Vec<_>>();
+            if !members.is_empty() {
+                return members;
+            }
+        }
+
+        // Any item whose path ends with the symbol, e.g. `Mutex` -> `tokio::sync::Mutex`
+        self.find_by_suffix(dependency, dep_docs, &symbol)
+            .into_iter()
+            .filter(|(_, item)| matches_kind(item))
+            .map(|(path, item)| (path, annotated_markdown(item)))
+            .collect()
+    }
+
+    /// Where the items matching a symbol path are defined, according to the
+    /// "Source" links of their docs. Associated items like `Mutex::lock` have
+    /// no page of their own and are not found.
+    pub fn source_locations(
+        &self,
+        dependency: &str,
+        symbol: &str,
+        kind: Option<ItemKind>,
+    ) -> Vec<(String, SourceLocation)> {
+        let Some(dep_docs) = self.cache.deps.get(dependency) else {
+            return Vec::new();
+        };
+        let (symbol_kind, symbol) = parse_symbol_query(symbol);
+        let kind = kind.or(symbol_kind);
+        let full_path = if symbol == dependency || symbol.starts_with(&format!("{dependency}::")) {
+            symbol.clone()
+        } else {
+            format!("{dependency}::{symbol}")
+        };
+
+        let exact = [
+            full_path.clone(),
+            format!("{full_path}!"),
+            format!("derive@{full_path}"),
+            format!("attr@{full_path}"),
+        ]
+        .iter()
+        .filter_map(|path| self.resolve(path))
+        .collect::<Vec<_>>();
+        let matches = if exact.is_empty() {
+            self.find_by_suffix(dependency, dep_docs, &symbol)
+        } else {
+            exact
+        };
+        matches
+            .into_iter()
+            .filter(|(_, item)| kind.is_none_or(|kind| item.kind == kind))
+            .filter_map(|(path, item)| Some((key_path(&path).to_string(), item.source.clone()?)))
+            .collect()
+    }
+
+    /// Follows re-exports until an item with docs is found
+    fn resolve(&self, path: &str) -> Option<(String, &DocsItem)> {
+        let mut path = path.to_string();
+        // Guard against cycles in the re-exports
+        for _ in 0..8 {
+            let crate_name = path.split("::").next()?;
+            if let Some(item) = self.cache.deps.get(crate_name).and_then(|d| d.get(&path)) {
+                return Some((path, item));
+            }
+            path = self.cache.aliases.get(crate_name)?.get(&path)?.clone();
+        }
+        None
+    }
+
+    fn find_by_suffix<'a>(
+        &'a self,
+        dependency: &str,
+        dep_docs: &'a HashMap<String, DocsItem>,
+        suffix: &str,
+    ) -> Vec<(String, &'a DocsItem)> {
+        let suffixes = [format!("::{suffix}"), format!("::{suffix}!")];
+        let mut matches = dep_docs
+            .iter()
+            .filter(|(path, _)| suffixes.iter().any(|s| path.ends_with(s.as_str())))
+            .map(|(path, item)| (path.clone(), item))
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            // The item might only be reachable through a re-export
+            matches = self
+                .cache
+                .aliases
+                .get(dependency)
+                .into_iter()
+                .flatten()
+                .filter(|(alias, _)| suffixes.iter().any(|s| alias.ends_with(s.as_str())))
+                .filter_map(|(alias, _)| self.resolve(alias))
+                .collect();
+        }
+        matches.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| a.0.cmp(&b.0)));
+        matches.dedup_by(|a, b| a.0 == b.0);
+        matches.truncate(MAX_SUFFIX_MATCHES);
+        matches
+    }
+
+    /// All item paths of the dependency, for suggestions
+    pub fn item_paths(&self, dependency: &str) -> Vec<&str> {
         self.cache
             .deps
             .get(dependency)
-            .map(|symbols| symbols.keys().cloned().collect())
+            .map(|docs| docs.keys().map(|k| k.as_str()).collect())
+            .unwrap_or_default()
     }
 
-    pub fn docs(&self, dependency: &str, symbols: &[String]) -> Option<Vec<(String, String)>> {
+    /// Returns the crate-level documentation followed by a listing of the
+    /// modules and items of the crate. If `module` is given, only that module
+    /// (and its submodules) is described. If `kind` is given, only items of
+    /// that kind are listed.
+    pub fn overview(
+        &self,
+        dependency: &str,
+        module: Option<&str>,
+        kind: Option<ItemKind>,
+    ) -> Option<Vec<String>> {
         let dep_docs = self.cache.deps.get(dependency)?;
+        let module = full_module(dependency, module);
+
+        let mut sections = Vec::new();
+        if kind.is_none()
+            && let Some(item) = dep_docs.get(&module)
+        {
+            sections.push(annotated_markdown(item));
+        }
+        if kind.is_none()
+            && module == dependency
+            && let Some(features) = self.cache.crate_features.get(dependency)
+        {
+            let features = features
+                .iter()
+                .map(|f| format!("`{f}`"))
+                .collect::<Vec<_>>();
+            sections.push(format!(
+                "Features enabled by the project: {}\n",
+                if features.is_empty() {
+                    "none".to_string()
+                } else {
+                    features.join(", ")
+                }
+            ));
+        }
+
+        // Group the items by the module they live in. Without a filter, modules
+        // are listed as the headings of their own group
+        let mut modules: BTreeMap<&str, Vec<(&str, &DocsItem)>> = BTreeMap::new();
+        for (key, item) in dep_docs {
+            let Some((parent, name)) = key_path(key).rsplit_once("::") else {
+                continue;
+            };
+            let included = match kind {
+                Some(kind) => item.kind == kind,
+                None => item.kind != ItemKind::Module,
+            };
+            if !included || !in_module(parent, &module) {
+                continue;
+            }
+            modules.entry(parent).or_default().push((name, item));
+        }
+
+        for (module_name, mut items) in modules {
+            items.sort_unstable_by(|a, b| a.0.cmp(b.0).then_with(|| a.1.kind.cmp(&b.1.kind)));
+            let mut section = format!("## {module_name} ({} items)\n", items.len());
+            for (name, item) in items {
+                match &item.feature_gate {
+                    Some(gate) => section.push_str(&format!(
+                        "- {} {name} (requires {gate})\n",
+                        item.kind.as_str()
+                    )),
+                    None => section.push_str(&format!("- {} {name}\n", item.kind.as_str())),
+                }
+            }
+            sections.push(section);
+        }
+        Some(sections)
+    }
+
+    /// Returns the full Markdown documentation of every symbol of the
+    /// dependency, one section per symbol, sorted by symbol path.
+    pub fn markdown_docs(
+        &self,
+        dependency: &str,
+        module: Option<&str>,
+        kind: Option<ItemKind>,
+    ) -> Option<Vec<String>> {
+        let dep_docs = self.cache.deps.get(dependency)?;
+        let module = full_module(dependency, module);
+
+        let mut symbols = dep_docs
+            .iter()
+            .filter(|(key, item)| {
+                in_module(key_path(key), &module) && kind.is_none_or(|kind| item.kind == kind)
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
         Some(
             symbols
-                .iter()
-                .filter_map(|symbol| {
-                    let doc = dep_docs.get(symbol)?;
-                    Some((symbol.clone(), doc.clone()))
+                .into_iter()
+                .map(|(key, item)| {
+                    format!(
+                        "## {} {}\n{}\n",
+                        item.kind.as_str(),
+                        key_path(key),
+                        annotated_markdown(item)
+                    )
                 })
                 .collect(),
         )
     }
 
-    pub fn markdown_docs(&self, dependency: &str) -> Option<String> {
-        let mut output = String::new();
+    /// Names of all dependencies that have docs in the cache.
+    pub fn indexed_dependencies(&self) -> Vec<&str> {
+        self.cache.deps.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Maximum number of items returned for an ambiguous symbol
+const MAX_SUFFIX_MATCHES: usize = 10;
+
+/// A single page of a paginated docs response
+#[derive(Debug, PartialEq)]
+pub struct DocsPage {
+    pub content: String,
+    /// Cursor to pass to get the next page. `None` if this is the last page
+    pub next_cursor: Option<usize>,
+    pub total_sections: usize,
+}
 
-        let symbols = self.symbols(dependency)?;
-        for symbol in symbols {
-            if let Some(docs) = self.docs(dependency, &[symbol.clone()]) {
-                output.push_str(&symbol);
-                output.push('\n');
-                for (doc, content) in docs {
-                    output.push_str(&doc);
-                    output.push('\n');
-                    output.push_str(&content);
-                    output.push('\n');
+/// Joins `sections` starting at `cursor` until `max_bytes` is reached.
+/// Sections that don't fit into the budget on their own are split into
+/// several at line boundaries first, so every part can be reached with a
+/// cursor. Cursors count these parts, so they are only valid together with
+/// the same `max_bytes`.
+pub fn paginate(sections: &[String], cursor: usize, max_bytes: usize) -> DocsPage {
+    // Every part is followed by a newline, which counts towards the budget
+    let max_bytes = max_bytes.max(2);
+    let parts = split_oversized(sections, max_bytes - 1);
+    let mut content = String::new();
+    let mut next = cursor;
+    for part in parts.iter().skip(cursor) {
+        if !content.is_empty() && content.len() + part.len() + 1 > max_bytes {
+            break;
+        }
+        content.push_str(part);
+        content.push('\n');
+        next += 1;
+    }
+    DocsPage {
+        content,
+        next_cursor: (next < parts.len()).then_some(next),
+        total_sections: parts.len(),
+    }
+}
+
+/// Splits sections longer than `max_bytes` at line ends, and lines longer
+/// than that at char boundaries. Joining the parts of a section with
+/// newlines gives back the section.
+fn split_oversized(sections: &[String], max_bytes: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    for section in sections {
+        if section.len() <= max_bytes {
+            parts.push(section.as_str());
+            continue;
+        }
+        let mut start = 0;
+        let mut end = 0;
+        let mut line_start = 0;
+        for line in section.split('\n') {
+            let line_end = line_start + line.len();
+            if line_end - start > max_bytes && end > start {
+                parts.push(&section[start..end]);
+                start = line_start;
+            }
+            // A line that doesn't fit on its own
+            while line_end - start > max_bytes {
+                let mut split = start + max_bytes;
+                while !section.is_char_boundary(split) {
+                    split -= 1;
                 }
+                parts.push(&section[start..split]);
+                start = split;
             }
+            end = line_end;
+            line_start = line_end + 1;
         }
-        Some(output)
+        parts.push(&section[start..end]);
+    }
+    parts
+}
+
+/// Turns `tokio::sync`, `sync` or `sync/` into the full module path `tokio::sync`
+fn full_module(dependency: &str, module: Option<&str>) -> String {
+    let module = module.unwrap_or_default().trim().trim_matches('/').replace('/', "::");
+    let module = module.trim_end_matches("::");
+    if module.is_empty() || module == dependency {
+        dependency.to_string()
+    } else if module.starts_with(&format!("{dependency}::")) {
+        module.to_string()
+    } else {
+        format!("{dependency}::{module}")
+    }
+}
+
+/// Whether the item or module `path` is inside of `module` (or is `module`)
+fn in_module(path: &str, module: &str) -> bool {
+    path == module
+        || path
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.starts_with("::"))
+}
+
+/// Splits a symbol query into an optional kind and the path. Accepts
+/// `crate::path`, `derive@Serialize`, the legacy `struct Mutex` keys and
+/// `macro!` calls
+fn parse_symbol_query(symbol: &str) -> (Option<ItemKind>, String) {
+    let symbol = symbol.trim();
+    let (kind, path) = match symbol.split_once('@') {
+        Some((kind, path)) => (ItemKind::from_filter(kind), path),
+        None => match symbol.rsplit_once(' ') {
+            Some((kind, path)) => (ItemKind::from_filter(kind), path),
+            None => (None, symbol),
+        },
+    };
+    let path = path
+        .trim_start_matches("::")
+        .trim_end_matches("()")
+        .trim_end_matches('!')
+        .replace('-', "_");
+    (kind, path)
+}
+
+/// The docs of an item, prefixed with the feature it requires
+fn annotated_markdown(item: &DocsItem) -> String {
+    match &item.feature_gate {
+        Some(gate) => format!("Requires {gate}\n\n{}", item.markdown),
+        None => item.markdown.clone(),
+    }
+}
+
+/// The item path of a cache key, without the `derive@` / `attr@` prefix
+fn key_path(key: &str) -> &str {
+    key.split_once('@').map(|(_, path)| path).unwrap_or(key)
+}
+
+/// The part of `parent` to search for if it's not an exact path
+fn parent_suffix<'a>(dependency: &str, parent: &'a str) -> &'a str {
+    parent
+        .strip_prefix(dependency)
+        .and_then(|rest| rest.strip_prefix("::"))
+        .unwrap_or(parent)
+}
+
+/// Extracts the section documenting the method (or associated const / type)
+/// `member` from the Markdown of a type page
+fn member_section(markdown: &str, member: &str) -> Option<String> {
+    let pattern = Regex::new(&format!(r"\b(fn|const|type)\s+{}\b", regex::escape(member))).ok()?;
+    let lines = markdown.lines().collect::<Vec<_>>();
+    let start = lines
+        .iter()
+        .position(|line| heading_level(line) > 0 && pattern.is_match(line))?;
+    let level = heading_level(lines[start]);
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| (1..=level).contains(&heading_level(line)))
+        .map(|offset| start + 1 + offset)
+        .unwrap_or(lines.len());
+    Some(lines[start..end].join("\n"))
+}
+
+fn heading_level(line: &str) -> usize {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level > 0 && line[level..].starts_with(' ') {
+        level
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sections() -> Vec<String> {
+        vec!["a".repeat(10), "b".repeat(10), "c".repeat(10)]
+    }
+
+    #[test]
+    fn test_paginate() {
+        let page = paginate(&sections(), 0, 25);
+        assert_eq!(page.content, format!("{}\n{}\n", "a".repeat(10), "b".repeat(10)));
+        assert_eq!(page.next_cursor, Some(2));
+
+        let page = paginate(&sections(), 2, 25);
+        assert_eq!(page.content, format!("{}\n", "c".repeat(10)));
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn test_paginate_splits_oversized_section() {
+        let sections = vec!["one\ntwo\nthree".to_string(), "four".to_string()];
+        let page = paginate(&sections, 0, 9);
+        assert_eq!(page.content, "one\ntwo\n");
+        assert_eq!(page.next_cursor, Some(1));
+        assert_eq!(page.total_sections, 3);
+        let page = paginate(&sections, 1, 9);
+        assert_eq!(page.content, "three\n");
+        assert_eq!(page.next_cursor, Some(2));
+        let page = paginate(&sections, 2, 9);
+        assert_eq!(page.content, "four\n");
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn test_paginate_splits_long_line() {
+        let sections = vec!["aaaaäaa".to_string()];
+        let mut cursor = Some(0);
+        let mut content = String::new();
+        while let Some(next) = cursor {
+            let page = paginate(&sections, next, 5);
+            assert!(page.content.len() <= 5);
+            content.push_str(page.content.trim_end());
+            cursor = page.next_cursor;
+        }
+        assert_eq!(content, "aaaaäaa");
+    }
+
+    #[test]
+    fn test_full_module() {
+        assert_eq!(full_module("tokio", Some("tokio::sync")), "tokio::sync");
+        assert_eq!(full_module("tokio", Some("sync/mpsc/")), "tokio::sync::mpsc");
+        assert_eq!(full_module("tokio", Some("tokio")), "tokio");
+        assert_eq!(full_module("tokio", None), "tokio");
+        assert_eq!(full_module("tokio", Some("tokio_util")), "tokio::tokio_util");
+        assert!(in_module("tokio::sync::mpsc", "tokio::sync"));
+        assert!(!in_module("tokio::syncx", "tokio::sync"));
+    }
+
+    #[test]
+    fn test_member_section() {
+        let markdown = "# Struct Mutex\n\
+            ## Implementations\n\
+            #### pub fn new(t: T) -> Mutex<T>\n\
+            Creates a new lock.\n\
+            ##### Examples\n\
+            let m = Mutex::new(5);\n\
+            #### pub async fn lock(&self) -> MutexGuard<'_, T>\n\
+            Locks this mutex.\n\
+            ## Trait Implementations";
+        assert_eq!(
+            member_section(markdown, "new").as_deref(),
+            Some(
+                "#### pub fn new(t: T) -> Mutex<T>\nCreates a new lock.\n##### Examples\nlet m = Mutex::new(5);"
+            )
+        );
+        assert_eq!(
+            member_section(markdown, "lock").as_deref(),
+            Some("#### pub async fn lock(&self) -> MutexGuard<'_, T>\nLocks this mutex.")
+        );
+        assert_eq!(member_section(markdown, "unlock"), None);
     }
 }
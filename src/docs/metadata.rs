@@ -0,0 +1,159 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::project::Project;
+
+/// The parts of `cargo metadata --format-version 1` we care about
+#[derive(Debug, Clone, Deserialize)]
+pub struct CargoMetadata {
+    pub packages: Vec<Package>,
+    pub workspace_members: Vec<String>,
+    pub resolve: Option<Resolve>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub id: String,
+    pub manifest_path: PathBuf,
+    /// `registry+...` or `git+...`; `None` for path dependencies
+    pub source: Option<String>,
+    #[serde(default)]
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    pub kind: Vec<String>,
+    pub src_path: PathBuf,
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Resolve {
+    pub nodes: Vec<ResolveNode>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResolveNode {
+    pub id: String,
+    #[serde(default)]
+    pub deps: Vec<NodeDep>,
+    /// The features cargo enabled for this package
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeDep {
+    pub pkg: String,
+}
+
+/// A direct dependency of a workspace member, with the version and the
+/// features cargo resolved for the project
+#[derive(Debug, Clone)]
+pub struct ResolvedDependency {
+    pub name: String,
+    pub version: String,
+    pub features: Vec<String>,
+    pub manifest_path: PathBuf,
+    pub source: Option<String>,
+    /// The root file of the library target, e.g. `src/lib.rs`
+    pub lib_path: Option<PathBuf>,
+}
+
+impl ResolvedDependency {
+    /// The directory with the package sources, e.g. the unpacked crate in
+    /// the local cargo registry
+    pub fn root(&self) -> Option<&Path> {
+        self.manifest_path.parent()
+    }
+}
+
+pub fn cargo_metadata(project: &Project) -> Result<CargoMetadata> {
+    let output = Command::new("cargo")
+        .current_dir(project.root())
+        .args(["metadata", "--format-version", "1"])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to run cargo metadata: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+impl CargoMetadata {
+    pub fn workspace_packages(&self) -> Vec<&Package> {
+        self.packages
+            .iter()
+            .filter(|p| self.workspace_members.contains(&p.id))
+            .collect()
+    }
+
+    /// All direct dependencies (including dev and build dependencies) of the
+    /// workspace members, sorted by name
+    pub fn direct_dependencies(&self) -> Vec<ResolvedDependency> {
+        let Some(resolve) = &self.resolve else {
+            return Vec::new();
+        };
+        let packages: HashMap<&str, &Package> =
+            self.packages.iter().map(|p| (p.id.as_str(), p)).collect();
+        let nodes: HashMap<&str, &ResolveNode> =
+            resolve.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+
+        let mut seen = HashSet::new();
+        let mut dependencies = Vec::new();
+        for member in &self.workspace_members {
+            let Some(node) = nodes.get(member.as_str()) else {
+                continue;
+            };
+            for dep in &node.deps {
+                if self.workspace_members.contains(&dep.pkg) || !seen.insert(dep.pkg.as_str()) {
+                    continue;
+                }
+                let Some(package) = packages.get(dep.pkg.as_str()) else {
+                    continue;
+                };
+                dependencies.push(ResolvedDependency {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    manifest_path: package.manifest_path.clone(),
+                    source: package.source.clone(),
+                    lib_path: package
+                        .targets
+                        .iter()
+                        .find(|target| {
+                            target
+                                .kind
+                                .iter()
+                                .any(|kind| kind.ends_with("lib") || kind == "proc-macro")
+                        })
+                        .map(|target| target.src_path.clone()),
+                    features: nodes
+                        .get(dep.pkg.as_str())
+                        .map(|n| n.features.clone())
+                        .unwrap_or_default(),
+                });
+            }
+        }
+        dependencies.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+        dependencies
+    }
+
+    /// The direct dependency with the given name. Cargo allows `-` in package
+    /// names while rustdoc uses `_`, so both spellings match.
+    pub fn dependency(&self, name: &str) -> Option<ResolvedDependency> {
+        let name = name.replace('-', "_");
+        self.direct_dependencies()
+            .into_iter()
+            .find(|dep| dep.name.replace('-', "_") == name)
+    }
+}
@@ -1,16 +1,24 @@
 use std::{path::PathBuf, sync::Arc};
 
 use flume::Sender;
+use fuzzt::get_top_n;
 use generate::generate_docs;
+use index::{DocsPage, paginate};
+use metadata::cargo_metadata;
+use source::DependencySource;
+use utils::ItemKind;
 use tokio::sync::Mutex;
 use walk::walk_docs;
 
 use crate::project::Project;
 use anyhow::Result;
 
+pub mod examples;
 pub mod extract_md;
 pub mod generate;
 pub mod index;
+pub mod metadata;
+pub mod source;
 pub mod utils;
 pub mod walk;
 
@@ -19,6 +27,31 @@ pub enum DocsNotification {
     Indexing { project: PathBuf, is_indexing: bool },
 }
 
+/// Default size budget of a single `crate_docs` page
+const DEFAULT_MAX_BYTES: usize = 40_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CrateDocsMode {
+    /// Crate-level docs plus a listing of modules and items
+    #[default]
+    Overview,
+    /// The full docs of every item
+    Full,
+}
+
+/// Which part of a crate's documentation to return and how much of it
+#[derive(Debug, Clone, Default)]
+pub struct CrateDocsQuery {
+    pub mode: CrateDocsMode,
+    /// Restrict the docs to a module, e.g. `tokio::sync` or `sync`
+    pub module: Option<String>,
+    /// Restrict the docs to items of a kind, e.g. only traits
+    pub kind: Option<ItemKind>,
+    /// Section to start at, as returned in `DocsPage::next_cursor`
+    pub cursor: usize,
+    pub max_bytes: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct Docs {
     project: Project,
@@ -113,10 +146,17 @@ impl Docs {
         let cloned_index = self.index.clone();
         let cloned_notifier = self.notifier.clone();
         tokio::spawn(async move {
-            if let Err(e) = generate_docs(&cloned_project) {
+            let metadata = match cargo_metadata(&cloned_project) {
+                Ok(metadata) => Some(metadata),
+                Err(e) => {
+                    tracing::warn!("Failed to resolve dependencies with cargo metadata: {:?}", e);
+                    None
+                }
+            };
+            if let Err(e) = generate_docs(&cloned_project, metadata.as_ref()) {
                 tracing::error!("Failed to generate docs: {:?}", e);
             }
-            if let Err(e) = walk_docs(&cloned_project) {
+            if let Err(e) = walk_docs(&cloned_project, metadata.as_ref()) {
                 tracing::error!("Failed to update docs cache: {:?}", e);
             }
 
@@ -147,31 +187,202 @@ impl Docs {
         Ok(())
     }
 
-    pub async fn crate_docs(&self, crate_name: &str) -> Result<String> {
+    pub async fn crate_docs(&self, crate_name: &str, query: &CrateDocsQuery) -> Result<DocsPage> {
         let index = self.index.lock().await;
         if index.dependencies().is_empty() {
             return Err(anyhow::anyhow!(
                 "No dependencies found. Please update the docs cache first"
             ));
         }
-        let markdown = index.markdown_docs(crate_name).unwrap();
-        Ok(markdown)
+        let crate_name = resolve_crate_name(&index, crate_name)?;
+        let module = query.module.as_deref();
+        let sections = match query.mode {
+            CrateDocsMode::Overview => index.overview(&crate_name, module, query.kind),
+            CrateDocsMode::Full => index.markdown_docs(&crate_name, module, query.kind),
+        }
+        .unwrap_or_default();
+        if sections.is_empty() {
+            if let Some(kind) = query.kind {
+                return Err(anyhow::anyhow!(
+                    "No items of kind `{}` found in crate `{crate_name}`",
+                    kind.as_str()
+                ));
+            }
+            return Err(match module {
+                Some(module) => anyhow::anyhow!(
+                    "No docs found for module `{module}` in crate `{crate_name}`"
+                ),
+                None => anyhow::anyhow!("No docs found for crate `{crate_name}`"),
+            });
+        }
+        Ok(paginate(
+            &sections,
+            query.cursor,
+            query.max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        ))
     }
 
+    /// The docs of every item matching `symbol`, one section per item
     pub async fn crate_symbol_docs(
         &self,
         crate_name: &str,
         symbol: &str,
-    ) -> Result<Vec<(String, String)>> {
+        kind: Option<ItemKind>,
+        cursor: usize,
+        max_bytes: Option<usize>,
+    ) -> Result<DocsPage> {
         let index = self.index.lock().await;
         if index.dependencies().is_empty() {
             return Err(anyhow::anyhow!(
                 "No dependencies found. Please update the docs cache first"
             ));
         }
-        let Some(docs) = index.docs(crate_name, &[symbol.to_string()]) else {
-            return Err(anyhow::anyhow!("No docs found for crate: {}", crate_name));
-        };
-        Ok(docs)
+        let crate_name = resolve_crate_name(&index, crate_name)?;
+        let docs = index.lookup(&crate_name, symbol, kind);
+        if docs.is_empty() {
+            let paths = index.item_paths(&crate_name);
+            let similar = get_top_n(symbol, &paths, None, Some(5), None, None)
+                .iter()
+                .map(|path| format!("`{path}`"))
+                .collect::<Vec<_>>();
+            return Err(anyhow::anyhow!(
+                "No docs found for `{symbol}` in crate `{crate_name}`. Similar symbols: {}",
+                similar.join(", ")
+            ));
+        }
+        let sections: Vec<String> = docs
+            .into_iter()
+            .map(|(path, markdown)| format!("# {path}\n{markdown}\n"))
+            .collect();
+        Ok(paginate(
+            &sections,
+            cursor,
+            max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        ))
+    }
+
+    /// The doc-tests of a symbol (or of the crate root) followed by the files
+    /// in the `examples/` directory of the dependency sources, ranked by how
+    /// often they mention the symbol
+    pub async fn crate_examples(
+        &self,
+        crate_name: &str,
+        symbol: Option<&str>,
+        kind: Option<ItemKind>,
+        cursor: usize,
+        max_bytes: Option<usize>,
+    ) -> Result<DocsPage> {
+        let mut sections = Vec::new();
+        {
+            let index = self.index.lock().await;
+            if let Ok(crate_name) = resolve_crate_name(&index, crate_name) {
+                let docs = index.lookup(&crate_name, symbol.unwrap_or(&crate_name), kind);
+                for (path, markdown) in docs {
+                    let blocks = examples::code_blocks(&markdown);
+                    let count = blocks.len();
+                    for (i, block) in blocks.into_iter().enumerate() {
+                        sections.push(format!(
+                            "## Doc-test {}/{count} of `{path}`\n```rust\n{block}\n```\n",
+                            i + 1
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Ok(source) = self.dependency_source(crate_name).await {
+            let files =
+                examples::example_files(&source.root, symbol.map(examples::mention_name))?;
+            for file in files {
+                let mentions = match symbol {
+                    Some(symbol) => format!(" ({} mentions of `{symbol}`)", file.mentions),
+                    None => String::new(),
+                };
+                sections.push(format!(
+                    "## {}{mentions}\n```rust\n{}\n```\n",
+                    file.path.display(),
+                    file.content.trim_end()
+                ));
+            }
+        }
+
+        if sections.is_empty() {
+            return Err(match symbol {
+                Some(symbol) => anyhow::anyhow!(
+                    "No doc-tests or example files found for `{symbol}` in crate `{crate_name}`"
+                ),
+                None => anyhow::anyhow!("No doc-tests or example files found for crate `{crate_name}`"),
+            });
+        }
+        Ok(paginate(
+            &sections,
+            cursor,
+            max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        ))
+    }
+
+    /// Resolves the source directory of a dependency via `cargo metadata`
+    pub async fn dependency_source(&self, crate_name: &str) -> Result<DependencySource> {
+        let project = self.project.clone();
+        let name = crate_name.to_string();
+        tokio::task::spawn_blocking(move || {
+            let metadata = cargo_metadata(&project)?;
+            DependencySource::resolve(&metadata, &name)
+        })
+        .await?
+    }
+
+    /// Where the items matching `symbol` are defined, according to the docs
+    pub async fn source_locations(
+        &self,
+        crate_name: &str,
+        symbol: &str,
+        kind: Option<ItemKind>,
+    ) -> Vec<(String, walk::SourceLocation)> {
+        let index = self.index.lock().await;
+        match resolve_crate_name(&index, crate_name) {
+            Ok(crate_name) => index.source_locations(&crate_name, symbol, kind),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Finds the name under which `crate_name` is stored in the index. Cargo
+/// allows `-` in package names while rustdoc always uses `_`.
+/// Returns an error listing similar crates if the crate is unknown.
+fn resolve_crate_name(index: &index::DocsIndex, crate_name: &str) -> Result<String> {
+    let normalized = crate_name.replace('-', "_");
+    let indexed = index.indexed_dependencies();
+    if let Some(name) = indexed
+        .iter()
+        .find(|name| name.replace('-', "_") == normalized)
+    {
+        return Ok(name.to_string());
+    }
+
+    let mut candidates = indexed;
+    candidates.extend(index.dependencies().iter().map(|(name, _)| name.as_str()));
+    if candidates
+        .iter()
+        .any(|name| name.replace('-', "_") == normalized)
+    {
+        return Err(anyhow::anyhow!(
+            "No docs for crate `{crate_name}` yet. Please update the docs cache first"
+        ));
+    }
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let similar = get_top_n(crate_name, &candidates, None, Some(5), None, None)
+        .iter()
+        .map(|name| format!("`{name}`"))
+        .collect::<Vec<_>>();
+    if similar.is_empty() {
+        Err(anyhow::anyhow!("Unknown crate `{crate_name}`"))
+    } else {
+        Err(anyhow::anyhow!(
+            "Unknown crate `{crate_name}`. Similar crates: {}",
+            similar.join(", ")
+        ))
     }
 }
@@ -0,0 +1,162 @@
+use anyhow::Result;
+use ignore::WalkBuilder;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::metadata::CargoMetadata;
+
+/// Files larger than this are only returned in line ranges
+pub const MAX_FILE_BYTES: usize = 200_000;
+
+/// The resolved sources of a dependency: the unpacked crate in the cargo
+/// registry, a git checkout or a local path
+#[derive(Debug, Clone)]
+pub struct DependencySource {
+    pub name: String,
+    pub version: String,
+    /// `registry+...` or `git+...`; `None` for path dependencies
+    pub source: Option<String>,
+    /// The package directory, i.e. the directory of its `Cargo.toml`
+    pub root: PathBuf,
+    /// The directory of the crate root file, which rustdoc's source paths are relative to
+    pub src_root: PathBuf,
+}
+
+impl DependencySource {
+    pub fn resolve(metadata: &CargoMetadata, name: &str) -> Result<Self> {
+        let dependency = metadata
+            .dependency(name)
+            .ok_or_else(|| anyhow::anyhow!("`{name}` is not a dependency of this project"))?;
+        let root = dependency
+            .root()
+            .ok_or_else(|| anyhow::anyhow!("No source directory found for `{name}`"))?
+            .to_path_buf();
+        let src_root = dependency
+            .lib_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|path| path.to_path_buf())
+            .unwrap_or_else(|| root.join("src"));
+        Ok(Self {
+            name: dependency.name,
+            version: dependency.version,
+            source: dependency.source,
+            root,
+            src_root,
+        })
+    }
+
+    /// A short description of where the sources come from
+    pub fn origin(&self) -> &str {
+        match self.source.as_deref() {
+            Some(source) if source.starts_with("git+") => "git checkout",
+            Some(_) => "registry",
+            None => "path",
+        }
+    }
+
+    /// All files of the package, relative to its root. Honors `.gitignore`.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in WalkBuilder::new(&self.root).build() {
+            let entry = entry?;
+            if entry.file_type().is_some_and(|t| t.is_file()) {
+                let path = entry.path();
+                files.push(path.strip_prefix(&self.root).unwrap_or(path).to_path_buf());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Resolves a path relative to the package root (or an absolute path) and
+    /// makes sure it doesn't leave the package sources
+    pub fn file_path(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = self.root.join(path);
+        let canonical = dunce::canonicalize(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {e}", path.display()))?;
+        if !self.contains(&canonical) {
+            return Err(anyhow::anyhow!(
+                "{} is outside of the sources of `{}`",
+                path.display(),
+                self.name
+            ));
+        }
+        Ok(canonical)
+    }
+
+    /// Whether an absolute path is within the package sources
+    pub fn contains(&self, path: &Path) -> bool {
+        let root = dunce::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        path.starts_with(root)
+    }
+
+    /// The lines `start_line..=end_line` (1-based) of a file, prefixed with
+    /// their line numbers. Without a range the whole file is returned, unless
+    /// it exceeds `MAX_FILE_BYTES`.
+    pub fn read(
+        &self,
+        path: impl AsRef<Path>,
+        start_line: Option<usize>,
+        end_line: Option<usize>,
+    ) -> Result<String> {
+        let path = self.file_path(path)?;
+        let content = fs::read_to_string(&path)?;
+        if start_line.is_none() && end_line.is_none() && content.len() > MAX_FILE_BYTES {
+            return Err(anyhow::anyhow!(
+                "{} has {} bytes, which is more than {MAX_FILE_BYTES}. Please request a line range",
+                path.display(),
+                content.len()
+            ));
+        }
+        Ok(numbered_lines(
+            &content,
+            start_line.unwrap_or(1),
+            end_line.unwrap_or(usize::MAX),
+        ))
+    }
+}
+
+/// Lines `start..=end` (1-based) of `content`, prefixed with their line numbers
+fn numbered_lines(content: &str, start: usize, end: usize) -> String {
+    content
+        .lines()
+        .enumerate()
+        .skip(start.saturating_sub(1))
+        .take_while(|(i, _)| *i < end)
+        .map(|(i, line)| format!("{:>5} {line}", i + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_numbered_lines() {
+        let content = "a\nb\nc\nd";
+        assert_eq!(numbered_lines(content, 2, 3), "    2 b\n    3 c");
+        assert_eq!(numbered_lines(content, 0, usize::MAX).lines().count(), 4);
+        assert_eq!(numbered_lines(content, 5, 9), "");
+    }
+
+    #[test]
+    fn test_file_path_stays_in_root() {
+        let root = TestDir::new("dependency_source_test");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn f() {}").unwrap();
+        let source = DependencySource {
+            name: "dep".to_string(),
+            version: "1.0.0".to_string(),
+            source: None,
+            root: root.to_path_buf(),
+            src_root: root.join("src"),
+        };
+        assert!(source.file_path("src/lib.rs").is_ok());
+        assert!(source.file_path("../").is_err());
+        assert!(source.file_path("/etc/hostname").is_err());
+    }
+}
@@ -1,33 +1,193 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use toml::Value;
 
+/// The kind of item a rustdoc page documents. Serialized as the prefix
+/// rustdoc uses for the page, e.g. `struct` for `struct.Mutex.html`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ItemKind {
+    #[serde(rename = "mod")]
+    Module,
+    #[serde(rename = "fn")]
+    Function,
+    #[serde(rename = "macro")]
+    Macro,
+    #[serde(rename = "derive")]
+    Derive,
+    #[serde(rename = "attr")]
+    Attribute,
+    #[serde(rename = "struct")]
+    Struct,
+    #[serde(rename = "enum")]
+    Enum,
+    #[serde(rename = "union")]
+    Union,
+    #[serde(rename = "trait")]
+    Trait,
+    #[serde(rename = "traitalias")]
+    TraitAlias,
+    #[serde(rename = "type")]
+    Type,
+    #[serde(rename = "constant")]
+    Constant,
+    #[serde(rename = "static")]
+    Static,
+    #[serde(rename = "primitive")]
+    Primitive,
+    #[serde(rename = "keyword")]
+    Keyword,
+    #[serde(rename = "foreigntype")]
+    ForeignType,
+}
+
+impl ItemKind {
+    pub const ALL: &[ItemKind] = &[
+        ItemKind::Module,
+        ItemKind::Function,
+        ItemKind::Macro,
+        ItemKind::Derive,
+        ItemKind::Attribute,
+        ItemKind::Struct,
+        ItemKind::Enum,
+        ItemKind::Union,
+        ItemKind::Trait,
+        ItemKind::TraitAlias,
+        ItemKind::Type,
+        ItemKind::Constant,
+        ItemKind::Static,
+        ItemKind::Primitive,
+        ItemKind::Keyword,
+        ItemKind::ForeignType,
+    ];
+
+    /// The prefix rustdoc uses for pages of this kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Module => "mod",
+            ItemKind::Function => "fn",
+            ItemKind::Macro => "macro",
+            ItemKind::Derive => "derive",
+            ItemKind::Attribute => "attr",
+            ItemKind::Struct => "struct",
+            ItemKind::Enum => "enum",
+            ItemKind::Union => "union",
+            ItemKind::Trait => "trait",
+            ItemKind::TraitAlias => "traitalias",
+            ItemKind::Type => "type",
+            ItemKind::Constant => "constant",
+            ItemKind::Static => "static",
+            ItemKind::Primitive => "primitive",
+            ItemKind::Keyword => "keyword",
+            ItemKind::ForeignType => "foreigntype",
+        }
+    }
+
+    /// Parses a kind filter as a user (or LLM) would write it, e.g.
+    /// `trait`, `traits`, `functions`, `const` or `derive macros`
+    pub fn from_filter(filter: &str) -> Option<Self> {
+        let filter = filter.trim().to_lowercase().replace(['_', '-', ' '], "");
+        let filter = match filter.as_str() {
+            "module" | "modules" | "mods" => "mod",
+            "function" | "functions" | "fns" => "fn",
+            "macros" | "macrorules" => "macro",
+            "derives" | "derivemacro" | "derivemacros" => "derive",
+            "attribute" | "attributes" | "attrs" | "attributemacro" | "attributemacros" => "attr",
+            "typealias" | "typealiases" | "types" | "typedef" => "type",
+            "const" | "consts" | "constants" => "constant",
+            "traitaliases" => "traitalias",
+            "externtype" | "externtypes" | "foreigntypes" => "foreigntype",
+            other => other.strip_suffix('s').unwrap_or(other),
+        };
+        Self::ALL.iter().copied().find(|kind| kind.as_str() == filter)
+    }
+
+    /// Derive and attribute macros share their path with the trait or
+    /// function they belong to, so they are keyed with a `derive@` prefix
+    pub fn key_prefix(&self) -> Option<&'static str> {
+        match self {
+            ItemKind::Derive => Some("derive@"),
+            ItemKind::Attribute => Some("attr@"),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum RustSymbol<'a> {
+    Module(&'a str),
     Function(&'a str),
     Macro(&'a str),
+    Derive(&'a str),
+    Attribute(&'a str),
     Struct(&'a str),
+    Enum(&'a str),
+    Union(&'a str),
     Trait(&'a str),
+    TraitAlias(&'a str),
     Type(&'a str),
-    Enum(&'a str),
+    Constant(&'a str),
+    Static(&'a str),
+    Primitive(&'a str),
+    Keyword(&'a str),
+    ForeignType(&'a str),
 }
 
 impl RustSymbol<'_> {
-    #[allow(clippy::inherent_to_string)]
-    pub fn to_string(&self) -> String {
+    pub fn name(&self) -> &str {
         match self {
-            RustSymbol::Function(name) => format!("fn {}", name),
-            RustSymbol::Macro(name) => format!("macro {}!", name),
-            RustSymbol::Struct(name) => format!("struct {}", name),
-            RustSymbol::Trait(name) => format!("trait {}", name),
-            RustSymbol::Type(name) => format!("type {}", name),
-            RustSymbol::Enum(name) => format!("enum {}", name),
+            RustSymbol::Module(name)
+            | RustSymbol::Function(name)
+            | RustSymbol::Macro(name)
+            | RustSymbol::Derive(name)
+            | RustSymbol::Attribute(name)
+            | RustSymbol::Struct(name)
+            | RustSymbol::Enum(name)
+            | RustSymbol::Union(name)
+            | RustSymbol::Trait(name)
+            | RustSymbol::TraitAlias(name)
+            | RustSymbol::Type(name)
+            | RustSymbol::Constant(name)
+            | RustSymbol::Static(name)
+            | RustSymbol::Primitive(name)
+            | RustSymbol::Keyword(name)
+            | RustSymbol::ForeignType(name) => name,
+        }
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        match self {
+            RustSymbol::Module(_) => ItemKind::Module,
+            RustSymbol::Function(_) => ItemKind::Function,
+            RustSymbol::Macro(_) => ItemKind::Macro,
+            RustSymbol::Derive(_) => ItemKind::Derive,
+            RustSymbol::Attribute(_) => ItemKind::Attribute,
+            RustSymbol::Struct(_) => ItemKind::Struct,
+            RustSymbol::Enum(_) => ItemKind::Enum,
+            RustSymbol::Union(_) => ItemKind::Union,
+            RustSymbol::Trait(_) => ItemKind::Trait,
+            RustSymbol::TraitAlias(_) => ItemKind::TraitAlias,
+            RustSymbol::Type(_) => ItemKind::Type,
+            RustSymbol::Constant(_) => ItemKind::Constant,
+            RustSymbol::Static(_) => ItemKind::Static,
+            RustSymbol::Primitive(_) => ItemKind::Primitive,
+            RustSymbol::Keyword(_) => ItemKind::Keyword,
+            RustSymbol::ForeignType(_) => ItemKind::ForeignType,
         }
     }
 }
 
-pub fn parse_rust_symbol(filename: &str) -> Option<RustSymbol> {
+/// Parses the path of a rustdoc page, e.g. `struct.Mutex.html` or
+/// `sync/index.html`. Module pages are named after their folder; the
+/// crate root page (`index.html`) is a module with an empty name.
+pub fn parse_rust_symbol(path: &str) -> Option<RustSymbol> {
+    let (folder, filename) = path.rsplit_once('/').unwrap_or(("", path));
+    if filename == "index.html" {
+        let name = folder.rsplit('/').next().unwrap_or(folder);
+        return Some(RustSymbol::Module(name));
+    }
+
     // Split on the first dot to separate the kind from the name
     let parts: Vec<&str> = filename.splitn(2, '.').collect();
     if parts.len() != 2 {
@@ -45,14 +205,53 @@ pub fn parse_rust_symbol(filename: &str) -> Option<RustSymbol> {
     match kind {
         "fn" => Some(RustSymbol::Function(name)),
         "macro" => Some(RustSymbol::Macro(name)),
+        "derive" => Some(RustSymbol::Derive(name)),
+        "attr" => Some(RustSymbol::Attribute(name)),
         "struct" => Some(RustSymbol::Struct(name)),
+        "enum" => Some(RustSymbol::Enum(name)),
+        "union" => Some(RustSymbol::Union(name)),
         "trait" => Some(RustSymbol::Trait(name)),
+        "traitalias" => Some(RustSymbol::TraitAlias(name)),
         "type" => Some(RustSymbol::Type(name)),
-        "enum" => Some(RustSymbol::Enum(name)),
+        "constant" => Some(RustSymbol::Constant(name)),
+        "static" => Some(RustSymbol::Static(name)),
+        "primitive" => Some(RustSymbol::Primitive(name)),
+        "keyword" => Some(RustSymbol::Keyword(name)),
+        "foreigntype" => Some(RustSymbol::ForeignType(name)),
         _ => None,
     }
 }
 
+/// Turns the path of a rustdoc page relative to the crate docs folder into the
+/// cache key of the documented item and its kind,
+/// e.g. `sync/struct.Mutex.html` in `tokio` -> (`tokio::sync::Mutex`, `struct`).
+/// Macros get a trailing `!`, derive and attribute macros a `derive@` or
+/// `attr@` prefix. Returns `None` for pages that don't document an item, like
+/// `all.html`.
+pub fn item_path(crate_name: &str, file_path: &str) -> Option<(String, ItemKind)> {
+    let symbol = parse_rust_symbol(file_path)?;
+    let kind = symbol.kind();
+    let mut segments = vec![crate_name.to_string()];
+    segments.extend(
+        file_path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| segment.to_string()),
+    );
+    // The file name; module pages are named after their folder instead
+    segments.pop();
+    match symbol {
+        RustSymbol::Module(_) => {}
+        RustSymbol::Macro(name) => segments.push(format!("{name}!")),
+        _ => segments.push(symbol.name().to_string()),
+    }
+    let path = segments.join("::");
+    Some(match kind.key_prefix() {
+        Some(prefix) => (format!("{prefix}{path}"), kind),
+        None => (path, kind),
+    })
+}
+
 /// Get all dependencies from a Rust project. Supports workspaces as well.
 /// Returns a list of tuples with the dependency name and version.
 pub fn get_cargo_dependencies(project: &crate::project::Project) -> Result<Vec<(String, String)>> {
@@ -219,16 +418,76 @@ mod tests {
             parse_rust_symbol("trait.Context.html"),
             Some(RustSymbol::Trait("Context"))
         );
+        assert_eq!(
+            parse_rust_symbol("constant.MAX.html"),
+            Some(RustSymbol::Constant("MAX"))
+        );
+        assert_eq!(
+            parse_rust_symbol("static.GLOBAL.html"),
+            Some(RustSymbol::Static("GLOBAL"))
+        );
+        assert_eq!(
+            parse_rust_symbol("union.Bits.html"),
+            Some(RustSymbol::Union("Bits"))
+        );
+        assert_eq!(
+            parse_rust_symbol("derive.Serialize.html"),
+            Some(RustSymbol::Derive("Serialize"))
+        );
+        assert_eq!(
+            parse_rust_symbol("attr.main.html"),
+            Some(RustSymbol::Attribute("main"))
+        );
+        assert_eq!(
+            parse_rust_symbol("primitive.str.html"),
+            Some(RustSymbol::Primitive("str"))
+        );
+        assert_eq!(
+            parse_rust_symbol("keyword.async.html"),
+            Some(RustSymbol::Keyword("async"))
+        );
+        assert_eq!(
+            parse_rust_symbol("sync/mpsc/index.html"),
+            Some(RustSymbol::Module("mpsc"))
+        );
+        assert_eq!(parse_rust_symbol("index.html"), Some(RustSymbol::Module("")));
+        assert_eq!(parse_rust_symbol("all.html"), None);
         assert_eq!(parse_rust_symbol("invalid"), None);
     }
 
     #[test]
-    fn test_to_string() {
-        assert_eq!(RustSymbol::Function("Ok").to_string(), "fn Ok");
-        assert_eq!(RustSymbol::Macro("ensure").to_string(), "macro ensure!");
-        assert_eq!(RustSymbol::Struct("Chain").to_string(), "struct Chain");
-        assert_eq!(RustSymbol::Trait("Context").to_string(), "trait Context");
-        assert_eq!(RustSymbol::Type("Result").to_string(), "type Result");
-        assert_eq!(RustSymbol::Enum("Option").to_string(), "enum Option");
+    fn test_item_kind_from_filter() {
+        assert_eq!(ItemKind::from_filter("traits"), Some(ItemKind::Trait));
+        assert_eq!(ItemKind::from_filter("fn"), Some(ItemKind::Function));
+        assert_eq!(ItemKind::from_filter("Functions"), Some(ItemKind::Function));
+        assert_eq!(ItemKind::from_filter("const"), Some(ItemKind::Constant));
+        assert_eq!(ItemKind::from_filter("derive macros"), Some(ItemKind::Derive));
+        assert_eq!(ItemKind::from_filter("type alias"), Some(ItemKind::Type));
+        assert_eq!(ItemKind::from_filter("widgets"), None);
+    }
+
+    #[test]
+    fn test_item_path() {
+        assert_eq!(
+            item_path("tokio", "sync/struct.Mutex.html"),
+            Some(("tokio::sync::Mutex".to_string(), ItemKind::Struct))
+        );
+        assert_eq!(
+            item_path("tokio", "macro.select!.html"),
+            Some(("tokio::select!".to_string(), ItemKind::Macro))
+        );
+        assert_eq!(
+            item_path("serde", "derive.Serialize.html"),
+            Some(("derive@serde::Serialize".to_string(), ItemKind::Derive))
+        );
+        assert_eq!(
+            item_path("tokio", "sync/index.html"),
+            Some(("tokio::sync".to_string(), ItemKind::Module))
+        );
+        assert_eq!(
+            item_path("tokio", "index.html"),
+            Some(("tokio".to_string(), ItemKind::Module))
+        );
+        assert_eq!(item_path("tokio", "all.html"), None);
     }
 }
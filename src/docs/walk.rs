@@ -1,18 +1,67 @@
 use anyhow::Result;
 use ignore::WalkBuilder;
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self};
 use std::path::{Path, PathBuf};
 
-use super::extract_md::extract_md;
-use super::utils::{get_cargo_dependencies, parse_rust_symbol};
+use super::extract_md::{extract_feature_gate, extract_md, extract_source_location};
+use super::metadata::CargoMetadata;
+use super::utils::{ItemKind, get_cargo_dependencies, item_path};
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+/// Bump whenever the layout of the cache changes, so outdated caches get rebuilt
+pub const DOCS_CACHE_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocsItem {
+    pub kind: ItemKind,
+    pub markdown: String,
+    /// The `#[doc(cfg(...))]` gate of the item, e.g. "crate feature `sync`"
+    #[serde(default)]
+    pub feature_gate: Option<String>,
+    /// Where the item is defined in the crate sources
+    #[serde(default)]
+    pub source: Option<SourceLocation>,
+}
+
+/// A line range in a file of a crate, relative to the directory of the crate root
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    /// 1-based, inclusive
+    pub start_line: u32,
+    pub end_line: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct DocsCache {
+    #[serde(default)]
+    pub format_version: u32,
     pub hash: String,
-    pub deps: HashMap<String, HashMap<String, String>>,
+    /// Per crate: full item path (e.g. `tokio::sync::Mutex`) -> docs
+    pub deps: HashMap<String, HashMap<String, DocsItem>>,
     pub crate_versions: HashMap<String, String>,
+    /// Per crate: the features the project enables for it
+    #[serde(default)]
+    pub crate_features: HashMap<String, Vec<String>>,
+    /// Per crate: path of a re-export -> path of the item it points to
+    #[serde(default)]
+    pub aliases: HashMap<String, HashMap<String, String>>,
+}
+
+impl Default for DocsCache {
+    fn default() -> Self {
+        Self {
+            format_version: DOCS_CACHE_VERSION,
+            hash: String::new(),
+            deps: HashMap::new(),
+            crate_versions: HashMap::new(),
+            crate_features: HashMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
 }
 
 impl DocsCache {
@@ -20,7 +69,13 @@ impl DocsCache {
         let cache_path = project.cache_dir().join("docs_cache.json");
         if cache_path.exists() {
             let content = fs::read_to_string(cache_path)?;
-            Ok(serde_json::from_str(&content)?)
+            match serde_json::from_str::<Self>(&content) {
+                Ok(cache) if cache.format_version == DOCS_CACHE_VERSION => Ok(cache),
+                _ => {
+                    tracing::info!("Docs cache has an outdated format, rebuilding it");
+                    Ok(Self::default())
+                }
+            }
         } else {
             Ok(Self::default())
         }
@@ -34,14 +89,46 @@ impl DocsCache {
     }
 }
 
-pub fn walk_docs(project: &crate::project::Project) -> Result<()> {
+pub fn walk_docs(
+    project: &crate::project::Project,
+    metadata: Option<&CargoMetadata>,
+) -> Result<()> {
     let mut cache = DocsCache::new(project)?;
 
-    let dependencies = get_cargo_dependencies(project)?;
+    // Prefer the versions and features cargo resolved over the manifest
+    let dependencies = match metadata {
+        Some(metadata) => metadata
+            .direct_dependencies()
+            .into_iter()
+            .map(|d| (d.name, d.version, d.features))
+            .collect::<Vec<_>>(),
+        None => get_cargo_dependencies(project)?
+            .into_iter()
+            .map(|(name, version)| (name, version, Vec::new()))
+            .collect(),
+    };
     tracing::info!("dependencies: {:?}", dependencies);
 
-    // Convert dependencies to a HashMap for easier lookup
-    let dep_versions: HashMap<String, String> = dependencies.into_iter().collect();
+    // Convert dependencies to a HashMap for easier lookup. Rustdoc folders
+    // always use `_` even if the package name contains `-`
+    let mut dep_versions: HashMap<String, String> = HashMap::new();
+    let mut dep_features: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, version, features) in dependencies {
+        let name = name.replace('-', "_");
+        dep_versions.insert(name.clone(), version);
+        dep_features.insert(name, features);
+    }
+
+    // Crates whose docs are already cached for the current version and features
+    let up_to_date: HashSet<String> = dep_versions
+        .iter()
+        .filter(|(name, version)| {
+            cache.crate_versions.get(*name) == Some(*version)
+                && cache.crate_features.get(*name) == dep_features.get(*name)
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut refreshed = HashSet::new();
 
     // Walk the docs directory
     let walker = WalkBuilder::new(project.docs_dir()).hidden(false).build();
@@ -68,34 +155,75 @@ pub fn walk_docs(project: &crate::project::Project) -> Result<()> {
                     }
 
                     // Skip if version hasn't changed
-                    if let Some(cached_version) = cache.crate_versions.get(crate_name) {
-                        if cached_version == version {
-                            tracing::debug!(
-                                "Skipping {crate_name} because the version has not changed"
-                            );
-                            continue;
-                        }
+                    if up_to_date.contains(crate_name) {
+                        tracing::debug!(
+                            "Skipping {crate_name} because the version has not changed"
+                        );
+                        continue;
+                    }
+
+                    // Drop the docs of the previous version before indexing the new one
+                    if refreshed.insert(crate_name.to_string()) {
+                        cache.deps.remove(crate_name);
+                        cache.aliases.remove(crate_name);
                     }
 
                     // Process the file since it's either new or updated
                     let html_content = fs::read_to_string(path)?;
+
+                    // Rustdoc writes redirect pages for items that are re-exported
+                    if let Some(target) = redirect_target(&html_content) {
+                        let alias = item_path(crate_name, file_path);
+                        let target = resolve_redirect(crate_name, file_path, target);
+                        if let (Some((alias, _)), Some(target)) = (alias, target) {
+                            cache
+                                .aliases
+                                .entry(crate_name.to_string())
+                                .or_default()
+                                .insert(alias, target);
+                        }
+                        continue;
+                    }
+
+                    let Some((item, kind)) = item_path(crate_name, file_path) else {
+                        tracing::debug!("Skipping {crate_name}: {file_path} as it's not an item");
+                        continue;
+                    };
                     let markdown = extract_md(&html_content);
+                    let feature_gate = extract_feature_gate(&html_content);
+                    let source = extract_source_location(&html_content);
                     tracing::debug!("Indexing {crate_name}: {file_path}");
 
-                    let symbol = parse_rust_symbol(file_path)
-                        .map(|s| s.to_string())
-                        .unwrap_or(file_path.to_string());
+                    if kind == ItemKind::Module {
+                        cache
+                            .aliases
+                            .entry(crate_name.to_string())
+                            .or_default()
+                            .extend(module_reexports(&item, &markdown));
+                    }
 
                     cache
                         .deps
                         .entry(crate_name.to_string())
                         .or_default()
-                        .insert(symbol, markdown);
+                        .insert(
+                            item,
+                            DocsItem {
+                                kind,
+                                markdown,
+                                feature_gate,
+                                source,
+                            },
+                        );
 
-                    // Store the version number
+                    // Store the version number and features
                     cache
                         .crate_versions
                         .insert(crate_name.to_string(), version.clone());
+                    cache.crate_features.insert(
+                        crate_name.to_string(),
+                        dep_features.get(crate_name).cloned().unwrap_or_default(),
+                    );
                 }
             }
         }
@@ -107,6 +235,66 @@ pub fn walk_docs(project: &crate::project::Project) -> Result<()> {
     Ok(())
 }
 
+/// Returns the url of a rustdoc redirect page
+fn redirect_target(html: &str) -> Option<&str> {
+    let start = html.find(r#"http-equiv="refresh" content="0;URL="#)?;
+    let rest = &html[start + r#"http-equiv="refresh" content="0;URL="#.len()..];
+    rest.split('"').next()
+}
+
+/// Resolves the target of a redirect page at `file_path` to an item path
+fn resolve_redirect(crate_name: &str, file_path: &str, target: &str) -> Option<String> {
+    let mut segments = vec![crate_name];
+    segments.extend(file_path.split('/'));
+    // Drop the file name, urls are relative to the folder of the page
+    segments.pop();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop()?;
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    let (target_crate, rest) = segments.split_first()?;
+    item_path(target_crate, &rest.join("/")).map(|(path, _)| path)
+}
+
+lazy_static! {
+    /// `pub use path::Item;` or `pub use path::Item as Name;`
+    static ref REEXPORT: Regex = Regex::new(
+        r"pub use ((?:[A-Za-z_][A-Za-z0-9_]*::)*[A-Za-z_][A-Za-z0-9_]*)(?: as ([A-Za-z_][A-Za-z0-9_]*))?;",
+    )
+    .unwrap();
+}
+
+/// Collects the `pub use` re-exports listed on a module page
+fn module_reexports(module: &str, markdown: &str) -> Vec<(String, String)> {
+    let crate_name = module.split("::").next().unwrap_or(module);
+    REEXPORT
+        .captures_iter(markdown)
+        .filter_map(|caps| {
+            let target = caps.get(1)?.as_str();
+            let name = caps
+                .get(2)
+                .map(|m| m.as_str())
+                .or_else(|| target.rsplit("::").next())?;
+            let target = if let Some(rest) = target.strip_prefix("crate::") {
+                format!("{crate_name}::{rest}")
+            } else if let Some(rest) = target.strip_prefix("self::") {
+                format!("{module}::{rest}")
+            } else if let Some(rest) = target.strip_prefix("super::") {
+                let parent = module.rsplit_once("::").map(|(p, _)| p)?;
+                format!("{parent}::{rest}")
+            } else {
+                target.to_string()
+            };
+            Some((format!("{module}::{name}"), target))
+        })
+        .collect()
+}
+
 fn path_to_cache_key(path: &Path, docs_dir: PathBuf) -> Option<String> {
     path.strip_prefix(docs_dir)
         .ok()
@@ -121,3 +309,37 @@ fn extract_crate_and_path(path: &str) -> Option<(&str, &str)> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_redirect() {
+        let html = r#"<meta http-equiv="refresh" content="0;URL=../../../tokio/sync/struct.Mutex.html">"#;
+        let target = redirect_target(html).unwrap();
+        assert_eq!(target, "../../../tokio/sync/struct.Mutex.html");
+        assert_eq!(
+            resolve_redirect("tokio", "sync/mutex/struct.Mutex.html", target),
+            Some("tokio::sync::Mutex".to_string())
+        );
+    }
+
+    #[test]
+    fn test_module_reexports() {
+        let markdown = "## Re-exports\n`pub use serde_derive::Serialize;`\n`pub use self::inner::Thing as Other;`\n`pub use crate::de::*;`";
+        assert_eq!(
+            module_reexports("serde::ser", markdown),
+            vec![
+                (
+                    "serde::ser::Serialize".to_string(),
+                    "serde_derive::Serialize".to_string()
+                ),
+                (
+                    "serde::ser::Other".to_string(),
+                    "serde::ser::inner::Thing".to_string()
+                ),
+            ]
+        );
+    }
+}
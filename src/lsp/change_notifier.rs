@@ -1,34 +1,65 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 use anyhow::Result;
-use async_lsp::{LanguageServer, ServerSocket};
+use async_lsp::LanguageServer;
+use ignore::{
+    Match, WalkBuilder,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
 use lsp_types::{DidChangeWatchedFilesParams, FileChangeType, FileEvent};
 use notify_debouncer_mini::{
     DebounceEventResult, DebouncedEvent, Debouncer, new_debouncer, notify::*,
 };
-use tokio::{runtime::Handle, sync::Mutex};
 use url::Url;
+use super::supervisor::Supervisor;
 use crate::project::Project;
 
 #[derive(Debug)]
 pub struct ChangeNotifier {
     #[allow(dead_code)] // Keep the handle to ensure the change notifier runs
     debouncer: Debouncer<RecommendedWatcher>,
+    supervisor: Arc<Supervisor>,
+    /// While paused, changes are collected in `pending` instead of being sent
+    paused: Arc<AtomicBool>,
+    pending: Arc<std::sync::Mutex<Vec<FileEvent>>>,
 }
 
 impl ChangeNotifier {
     pub fn new(
-        server: Arc<Mutex<ServerSocket>>,
+        supervisor: Arc<Supervisor>,
         project: &Project,
-        handle: Handle,
     ) -> Result<Self> {
-        let handle_clone = handle.clone();
-        let target_path = project.root().join("target");
+        let supervisor_clone = supervisor.clone();
+        let mut watched_files = WatchedFiles::scan(project.root(), project.cache_folder());
+        let paused = Arc::new(AtomicBool::new(false));
+        let pending = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let paused_clone = paused.clone();
+        let pending_clone = pending.clone();
         let mut debouncer = new_debouncer(
             Duration::from_secs(2),
             move |res: DebounceEventResult| match res {
-                Ok(events) => events.iter().for_each(|e| {
-                    handle_event(e, server.clone(), handle_clone.clone(), target_path.clone())
-                }),
+                Ok(events) => {
+                    let changes = watched_files.changes(&events);
+                    if changes.is_empty() {
+                        return;
+                    }
+                    if paused_clone.load(Ordering::SeqCst) {
+                        pending_clone
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .extend(changes);
+                    } else {
+                        send_changes(changes, &supervisor_clone);
+                    }
+                }
                 Err(e) => tracing::error!("Error {:?}", e),
             },
         )?;
@@ -36,37 +67,379 @@ impl ChangeNotifier {
         debouncer
             .watcher()
             .watch(project.root(), RecursiveMode::Recursive)?;
-        Ok(Self { debouncer })
+        Ok(Self {
+            debouncer,
+            supervisor,
+            paused,
+            pending,
+        })
+    }
+
+    /// Holds back file changes while paused. Resuming sends the changes
+    /// collected in the meantime.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+        if paused {
+            return;
+        }
+        let pending =
+            std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+        let changes = merge_changes(pending);
+        if !changes.is_empty() {
+            tracing::debug!("Sending {} file changes from while paused", changes.len());
+            send_changes(changes, &self.supervisor);
+        }
     }
 }
 
-fn handle_event(
-    event: &DebouncedEvent,
-    server: Arc<Mutex<ServerSocket>>,
-    handle: Handle,
-    target_path: PathBuf,
-) {
-    // Don't trigger lsp on target files. Otherwise it will trigger itself.
-    if event.path.starts_with(&target_path) {
-        return;
+/// Files with ignore rules in the directory they're in, `.ignore` taking
+/// precedence over `.gitignore` like in the scan
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+/// The files of the project that rust-analyzer should know about. Tracking
+/// them tells creations apart from changes, which the debouncer doesn't report.
+#[derive(Debug)]
+struct WatchedFiles {
+    root: PathBuf,
+    /// Directories that are never watched, relative to the root
+    skipped: Vec<PathBuf>,
+    /// One matcher per `.ignore` and `.gitignore` file, each relative to its
+    /// own directory. Deeper directories come first.
+    ignore_files: Vec<(PathBuf, Gitignore)>,
+    /// `.git/info/exclude` and the global excludes of git
+    excludes: Vec<Gitignore>,
+    known: HashSet<PathBuf>,
+}
+
+impl WatchedFiles {
+    /// Collects the files below `root` and the ignore rules the scan applies
+    /// to them, so that later changes are filtered the same way
+    fn scan(root: &Path, cache_folder: &str) -> Self {
+        let repository = root
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .unwrap_or(root);
+        let mut files = Self {
+            root: root.to_path_buf(),
+            // Our own docs generation writes to the cache folder, and
+            // rust-analyzer to target. Either would trigger itself.
+            skipped: [".git", "target", cache_folder].map(PathBuf::from).to_vec(),
+            ignore_files: Vec::new(),
+            excludes: excludes(repository),
+            known: HashSet::new(),
+        };
+        // The scan also reads the ignore files of the parent directories
+        for dir in root.ancestors().skip(1).take_while(|dir| dir.starts_with(repository)) {
+            for name in IGNORE_FILES {
+                let path = dir.join(name);
+                if path.is_file() {
+                    files.add_ignore_file(&path);
+                }
+            }
+        }
+        let walk = WalkBuilder::new(root)
+            .hidden(false)
+            // Live changes are filtered by `.gitignore` outside of git repositories, too
+            .require_git(false)
+            .filter_entry({
+                let root = files.root.clone();
+                let skipped = files.skipped.clone();
+                move |entry| !is_skipped(&root, &skipped, entry.path())
+            })
+            .build();
+        for entry in walk.flatten() {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.into_path();
+            if is_ignore_file(&path) {
+                files.add_ignore_file(&path);
+            }
+            files.known.insert(path);
+        }
+        files
     }
-    tracing::trace!("Event {:?} for {:?}", event.kind, event.path);
-    let url = match Url::from_file_path(event.path.clone()) {
-        Ok(url) => url,
+
+    fn add_ignore_file(&mut self, path: &Path) {
+        let (gitignore, error) = Gitignore::new(path);
+        if let Some(e) = error {
+            tracing::debug!("Failed to parse {:?}: {}", path, e);
+        }
+        self.ignore_files.retain(|(existing, _)| existing != path);
+        self.ignore_files.push((path.to_path_buf(), gitignore));
+        self.ignore_files.sort_by_key(|(path, _)| {
+            (
+                Reverse(path.components().count()),
+                path.file_name().is_none_or(|name| name != ".ignore"),
+            )
+        });
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_skipped(&self.root, &self.skipped, path) {
+            return true;
+        }
+        // The closest rule decides, so a deeper `!pattern` can re-include a file
+        let matchers = self
+            .ignore_files
+            .iter()
+            .map(|(_, gitignore)| gitignore)
+            .chain(&self.excludes)
+            .filter(|gitignore| path.starts_with(gitignore.path()));
+        for gitignore in matchers {
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        false
+    }
+
+    /// The file events for a debounced batch of changed paths
+    fn changes(&mut self, events: &[DebouncedEvent]) -> Vec<FileEvent> {
+        let mut changes = Vec::new();
+        for event in events {
+            tracing::trace!("Event {:?} for {:?}", event.kind, event.path);
+            let path = &event.path;
+            let is_dir = path.is_dir();
+            // rust-analyzer only watches files
+            if is_dir || self.is_ignored(path, is_dir) {
+                continue;
+            }
+            if path.exists() {
+                if is_ignore_file(path) {
+                    self.add_ignore_file(path);
+                }
+                let typ = if self.known.insert(path.clone()) {
+                    FileChangeType::CREATED
+                } else {
+                    FileChangeType::CHANGED
+                };
+                changes.extend(file_event(path, typ));
+            } else {
+                // A removed directory takes the files below it along
+                let removed: Vec<PathBuf> = self
+                    .known
+                    .iter()
+                    .filter(|known| known.starts_with(path))
+                    .cloned()
+                    .collect();
+                self.ignore_files
+                    .retain(|(ignore_file, _)| !ignore_file.starts_with(path));
+                for removed in removed {
+                    self.known.remove(&removed);
+                    changes.extend(file_event(&removed, FileChangeType::DELETED));
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// The repository's `.git/info/exclude` and the global excludes, both
+/// relative to the repository root
+fn excludes(repository: &Path) -> Vec<Gitignore> {
+    let path = repository.join(".git/info/exclude");
+    let mut exclude = GitignoreBuilder::new(repository);
+    let exclude_error = path.is_file().then(|| exclude.add(&path)).flatten();
+    let exclude = exclude.build().unwrap_or_else(|e| {
+        tracing::debug!("Failed to build {:?}: {}", path, e);
+        Gitignore::empty()
+    });
+    let (global, global_error) = GitignoreBuilder::new(repository).build_global();
+    for error in [exclude_error, global_error].into_iter().flatten() {
+        tracing::debug!("Failed to parse git excludes: {}", error);
+    }
+    vec![exclude, global]
+}
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| IGNORE_FILES.iter().any(|ignore| name == *ignore))
+}
+
+/// One event per file for a series of events: whether the file existed before
+/// the first one and whether it exists after the last one. A file created and
+/// deleted in between is left out.
+fn merge_changes(changes: Vec<FileEvent>) -> Vec<FileEvent> {
+    let mut merged: Vec<(Url, bool, bool)> = Vec::new();
+    for change in changes {
+        let exists = change.typ != FileChangeType::DELETED;
+        match merged.iter_mut().find(|(uri, ..)| *uri == change.uri) {
+            Some((_, _, exists_after)) => *exists_after = exists,
+            None => {
+                let existed = change.typ != FileChangeType::CREATED;
+                merged.push((change.uri, existed, exists));
+            }
+        }
+    }
+    merged
+        .into_iter()
+        .filter_map(|(uri, existed, exists)| {
+            let typ = match (existed, exists) {
+                (true, true) => FileChangeType::CHANGED,
+                (false, true) => FileChangeType::CREATED,
+                (true, false) => FileChangeType::DELETED,
+                (false, false) => return None,
+            };
+            Some(FileEvent::new(uri, typ))
+        })
+        .collect()
+}
+
+fn is_skipped(root: &Path, skipped: &[PathBuf], path: &Path) -> bool {
+    path.strip_prefix(root)
+        .is_ok_and(|relative| skipped.iter().any(|dir| relative.starts_with(dir)))
+}
+
+fn file_event(path: &Path, typ: FileChangeType) -> Option<FileEvent> {
+    match Url::from_file_path(path) {
+        Ok(url) => Some(FileEvent::new(url, typ)),
         Err(e) => {
             tracing::error!("Failed to convert file path to URL: {:?}", e);
-            return;
+            None
         }
-    };
-    handle.spawn(async move {
-        match server
-            .lock()
-            .await
-            .did_change_watched_files(DidChangeWatchedFilesParams {
-                changes: vec![FileEvent::new(url, FileChangeType::CHANGED)],
-            }) {
-            Ok(_) => (),
-            Err(e) => tracing::error!("Failed to send DidChangeWatchedFiles notification: {:?}", e),
+    }
+}
+
+fn send_changes(changes: Vec<FileEvent>, supervisor: &Supervisor) {
+    // Notifications are queued without waiting for any request in flight
+    if let Err(e) = supervisor
+        .server()
+        .did_change_watched_files(DidChangeWatchedFilesParams { changes })
+    {
+        tracing::error!("Failed to send DidChangeWatchedFiles notification: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+    use notify_debouncer_mini::DebouncedEventKind;
+    use std::fs;
+
+    fn changes(files: &mut WatchedFiles, paths: &[&Path]) -> Vec<(String, FileChangeType)> {
+        let events: Vec<_> = paths
+            .iter()
+            .map(|path| DebouncedEvent::new(path.to_path_buf(), DebouncedEventKind::Any))
+            .collect();
+        files
+            .changes(&events)
+            .into_iter()
+            .map(|event| {
+                let path = event.uri.to_file_path().unwrap();
+                let relative = path.strip_prefix(&files.root).unwrap();
+                (relative.to_string_lossy().replace('\\', "/"), event.typ)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_watched_file_changes() {
+        let root = TestDir::new("change_notifier_test");
+        fs::create_dir_all(root.join("src/module")).unwrap();
+        fs::create_dir_all(root.join("generated")).unwrap();
+        fs::create_dir_all(root.join(".docs-cache")).unwrap();
+        fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "mod module;").unwrap();
+        fs::write(root.join("src/module/mod.rs"), "").unwrap();
+        let mut files = WatchedFiles::scan(&root, ".docs-cache");
+
+        fs::write(root.join("src/new.rs"), "").unwrap();
+        fs::write(root.join("generated/out.rs"), "").unwrap();
+        fs::write(root.join(".docs-cache/index.json"), "").unwrap();
+        assert_eq!(
+            changes(
+                &mut files,
+                &[
+                    &root.join("src/lib.rs"),
+                    &root.join("src/new.rs"),
+                    &root.join("generated/out.rs"),
+                    &root.join(".docs-cache/index.json"),
+                ]
+            ),
+            vec![
+                ("src/lib.rs".to_string(), FileChangeType::CHANGED),
+                ("src/new.rs".to_string(), FileChangeType::CREATED),
+            ]
+        );
+
+        fs::remove_dir_all(root.join("src/module")).unwrap();
+        assert_eq!(
+            changes(&mut files, &[&root.join("src/module")]),
+            vec![("src/module/mod.rs".to_string(), FileChangeType::DELETED)]
+        );
+        // Deleted paths are only reported once
+        assert!(changes(&mut files, &[&root.join("src/module")]).is_empty());
+    }
+
+    #[test]
+    fn test_ignore_files() {
+        let root = TestDir::new("change_notifier_ignore_test");
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("scratch")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "local.rs\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.gen.rs\n").unwrap();
+        fs::write(root.join(".ignore"), "scratch/\n").unwrap();
+        fs::write(root.join("src/.ignore"), "!kept.gen.rs\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        let mut files = WatchedFiles::scan(&root, ".docs-cache");
+
+        for file in ["scratch/a.rs", "local.rs", "src/out.gen.rs", "src/kept.gen.rs"] {
+            fs::write(root.join(file), "").unwrap();
         }
-    });
+        assert_eq!(
+            changes(
+                &mut files,
+                &[
+                    &root.join("src/lib.rs"),
+                    &root.join("scratch/a.rs"),
+                    &root.join("local.rs"),
+                    &root.join("src/out.gen.rs"),
+                    &root.join("src/kept.gen.rs"),
+                ]
+            ),
+            vec![
+                ("src/lib.rs".to_string(), FileChangeType::CHANGED),
+                ("src/kept.gen.rs".to_string(), FileChangeType::CREATED),
+            ]
+        );
+
+        // A changed `.ignore` applies to the following events
+        fs::write(root.join(".ignore"), "src/lib.rs\n").unwrap();
+        changes(&mut files, &[&root.join(".ignore")]);
+        assert!(changes(&mut files, &[&root.join("src/lib.rs")]).is_empty());
+        assert_eq!(
+            changes(&mut files, &[&root.join("scratch/a.rs")]),
+            vec![("scratch/a.rs".to_string(), FileChangeType::CREATED)]
+        );
+    }
+
+    #[test]
+    fn test_merge_changes() {
+        let event = |path: &str, typ| FileEvent::new(Url::parse(path).unwrap(), typ);
+        let changes = vec![
+            event("file:///project/created.rs", FileChangeType::CREATED),
+            event("file:///project/changed.rs", FileChangeType::CHANGED),
+            event("file:///project/temporary.rs", FileChangeType::CREATED),
+            event("file:///project/created.rs", FileChangeType::CHANGED),
+            event("file:///project/replaced.rs", FileChangeType::DELETED),
+            event("file:///project/temporary.rs", FileChangeType::DELETED),
+            event("file:///project/replaced.rs", FileChangeType::CREATED),
+            event("file:///project/removed.rs", FileChangeType::CHANGED),
+            event("file:///project/removed.rs", FileChangeType::DELETED),
+        ];
+        assert_eq!(
+            merge_changes(changes),
+            vec![
+                event("file:///project/created.rs", FileChangeType::CREATED),
+                event("file:///project/changed.rs", FileChangeType::CHANGED),
+                event("file:///project/replaced.rs", FileChangeType::CHANGED),
+                event("file:///project/removed.rs", FileChangeType::DELETED),
+            ]
+        );
+    }
 }
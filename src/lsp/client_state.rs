@@ -2,13 +2,15 @@ use std::ops::ControlFlow;
 use std::path::PathBuf;
 
 use super::Stop;
-use crate::lsp::{LspNotification, IndexingProgress};
+use super::{LspNotification, SharedProgress, update_progress};
 use async_lsp::router::Router;
 use async_lsp::{LanguageClient, ResponseError};
+use futures::future::BoxFuture;
 use lsp_types::{
-    NumberOrString, ProgressParams, ProgressParamsValue, PublishDiagnosticsParams,
-    ShowMessageParams, WorkDoneProgress,
+    ConfigurationParams, NumberOrString, ProgressParams, ProgressParamsValue,
+    PublishDiagnosticsParams, ShowMessageParams, WorkDoneProgress,
 };
+use serde_json::Value;
 
 // Old and new token names.
 const RA_INDEXING_TOKENS: &[&str] = &[
@@ -21,6 +23,10 @@ pub struct ClientState {
     project: PathBuf,
     indexed_tx: Option<flume::Sender<()>>,
     notifier: flume::Sender<LspNotification>,
+    /// Shared with the LSP client so pausing and progress reports don't overwrite each other
+    progress: SharedProgress,
+    /// The `rust_analyzer` settings of the project
+    settings: Option<Value>,
 }
 
 impl LanguageClient for ClientState {
@@ -67,9 +73,7 @@ impl LanguageClient for ClientState {
             if is_work_done {
                 tracing::debug!("Rust-analyzer indexing work done event");
                 
-                // Create a complete progress notification
-                let mut progress = IndexingProgress::new(self.project.clone());
-                progress.complete_indexing();
+                let progress = update_progress(&self.progress, |progress| progress.complete_indexing());
                 
                 // Try to send the detailed progress notification
                 if let Err(e) = self.notifier.try_send(LspNotification::IndexingProgress(progress)) {
@@ -106,18 +110,18 @@ impl LanguageClient for ClientState {
                 tracing::debug!("Rust-analyzer indexing work progress: {:?} {:?}", 
                               progress_message, progress_percentage);
                 
-                // Create an in-progress notification with details
-                let mut progress = IndexingProgress::new(self.project.clone());
-                progress.start_indexing();
-                
-                // Add detailed information if available
-                if let Some(msg) = progress_message {
-                    progress.status_message = Some(msg);
-                }
-                
-                if let Some(percent) = progress_percentage {
-                    progress.progress_percentage = Some(percent);
-                }
+                let progress = update_progress(&self.progress, |progress| {
+                    if !progress.is_indexing {
+                        progress.start_indexing();
+                    }
+                    // Add detailed information if available
+                    if let Some(msg) = progress_message {
+                        progress.status_message = Some(msg);
+                    }
+                    if let Some(percent) = progress_percentage {
+                        progress.progress_percentage = Some(percent);
+                    }
+                });
                 
                 // Try to send the detailed progress notification
                 if let Err(e) = self.notifier.try_send(LspNotification::IndexingProgress(progress)) {
@@ -145,6 +149,18 @@ impl LanguageClient for ClientState {
         ControlFlow::Continue(())
     }
 
+    fn configuration(
+        &mut self,
+        params: ConfigurationParams,
+    ) -> BoxFuture<'static, Result<Vec<Value>, Self::Error>> {
+        let values = params
+            .items
+            .iter()
+            .map(|item| configuration_section(self.settings.as_ref(), item.section.as_deref()))
+            .collect();
+        Box::pin(async move { Ok(values) })
+    }
+
     fn publish_diagnostics(&mut self, _: PublishDiagnosticsParams) -> Self::NotifyResult {
         ControlFlow::Continue(())
     }
@@ -160,11 +176,15 @@ impl ClientState {
         indexed_tx: flume::Sender<()>,
         notifier: flume::Sender<LspNotification>,
         project: PathBuf,
+        progress: SharedProgress,
+        settings: Option<Value>,
     ) -> Router<Self> {
         let mut router = Router::from_language_client(ClientState {
             indexed_tx: Some(indexed_tx),
             notifier,
             project,
+            progress,
+            settings,
         });
         router.event(Self::on_stop);
         router
@@ -174,3 +194,44 @@ impl ClientState {
         ControlFlow::Break(Ok(()))
     }
 }
+
+/// The part of the settings rust-analyzer asks for with `workspace/configuration`.
+/// It asks for the `rust-analyzer` section, which is the whole settings object;
+/// deeper sections like `rust-analyzer.cargo` are looked up in it.
+fn configuration_section(settings: Option<&Value>, section: Option<&str>) -> Value {
+    let Some(settings) = settings else {
+        return Value::Null;
+    };
+    let Some(section) = section else {
+        return settings.clone();
+    };
+    let mut path = section.split('.');
+    if path.next() != Some("rust-analyzer") {
+        return Value::Null;
+    }
+    path.try_fold(settings, |value, key| value.get(key))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_configuration_section() {
+        let settings = json!({"cargo": {"features": "all"}, "procMacro": {"enable": true}});
+        assert_eq!(configuration_section(Some(&settings), Some("rust-analyzer")), settings);
+        assert_eq!(
+            configuration_section(Some(&settings), Some("rust-analyzer.cargo")),
+            json!({"features": "all"})
+        );
+        assert_eq!(
+            configuration_section(Some(&settings), Some("rust-analyzer.check")),
+            Value::Null
+        );
+        assert_eq!(configuration_section(Some(&settings), Some("editor")), Value::Null);
+        assert_eq!(configuration_section(None, Some("rust-analyzer")), Value::Null);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use lsp_types::{TextDocumentItem, VersionedTextDocumentIdentifier};
+use url::Url;
+
+/// What has to be sent to rust-analyzer for new buffer contents
+#[derive(Debug, PartialEq)]
+pub(super) enum DocumentUpdate {
+    Open(TextDocumentItem),
+    Change(VersionedTextDocumentIdentifier, String),
+    /// The document is open with the same contents already
+    Unchanged,
+}
+
+/// Documents opened in rust-analyzer with `didOpen`. While a document is
+/// open, rust-analyzer answers from its contents instead of the file on disk.
+#[derive(Debug, Default)]
+pub(super) struct OpenDocuments {
+    documents: HashMap<Url, TextDocumentItem>,
+}
+
+impl OpenDocuments {
+    /// Records new contents for a document, opening it if needed. Every
+    /// change bumps the version, as the LSP requires.
+    pub fn update(&mut self, uri: Url, text: String) -> DocumentUpdate {
+        match self.documents.get_mut(&uri) {
+            Some(document) if document.text == text => DocumentUpdate::Unchanged,
+            Some(document) => {
+                document.version += 1;
+                document.text = text.clone();
+                DocumentUpdate::Change(
+                    VersionedTextDocumentIdentifier::new(uri, document.version),
+                    text,
+                )
+            }
+            None => {
+                let document = TextDocumentItem::new(uri.clone(), "rust".into(), 1, text);
+                self.documents.insert(uri, document.clone());
+                DocumentUpdate::Open(document)
+            }
+        }
+    }
+
+    /// Forgets a document. Returns whether it was open.
+    pub fn close(&mut self, uri: &Url) -> bool {
+        self.documents.remove(uri).is_some()
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<&TextDocumentItem> {
+        self.documents.get(uri)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TextDocumentItem> {
+        self.documents.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_lifecycle() {
+        let uri = Url::parse("file:///project/src/lib.rs").unwrap();
+        let mut documents = OpenDocuments::default();
+
+        let DocumentUpdate::Open(document) = documents.update(uri.clone(), "fn a() {}".into())
+        else {
+            panic!("expected didOpen");
+        };
+        assert_eq!(document.version, 1);
+        assert_eq!(
+            documents.update(uri.clone(), "fn a() {}".into()),
+            DocumentUpdate::Unchanged
+        );
+        assert_eq!(
+            documents.update(uri.clone(), "fn b() {}".into()),
+            DocumentUpdate::Change(
+                VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                "fn b() {}".into()
+            )
+        );
+        assert_eq!(documents.get(&uri).unwrap().text, "fn b() {}");
+
+        assert!(documents.close(&uri));
+        assert!(!documents.close(&uri));
+        assert!(matches!(
+            documents.update(uri, "fn c() {}".into()),
+            DocumentUpdate::Open(_)
+        ));
+    }
+}
@@ -0,0 +1,1190 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use lsp_types::{
+    AnnotatedTextEdit, DocumentChangeOperation, DocumentChanges, FileChangeType, OneOf, Position,
+    RenameFileOptions, ResourceOp, TextDocumentItem, TextEdit, WorkspaceEdit,
+};
+use url::Url;
+
+use super::position_to_byte_offset;
+
+/// Lines of context around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+/// Above this many line pairs, files are diffed as a whole instead of line by line
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A file before and after a `WorkspaceEdit`
+#[derive(Debug, Clone, PartialEq)]
+struct FileState {
+    /// `None` if the file doesn't exist yet
+    original: Option<String>,
+    /// `None` if the file is deleted
+    new: Option<String>,
+    /// The version of the synced buffer the original is taken from, `None`
+    /// if it was read from disk
+    version: Option<i32>,
+    /// The file on disk when the plan was made. Differs from `original` if
+    /// the buffer has unsaved changes.
+    disk: Option<String>,
+}
+
+/// How applying an `EditPlan` changes a file
+#[derive(Debug, PartialEq)]
+pub struct FileChange<'a> {
+    pub path: &'a Path,
+    pub typ: FileChangeType,
+    /// The new contents, `None` if the file was deleted
+    pub content: Option<&'a str>,
+}
+
+/// The effect of a `WorkspaceEdit` on the files of a project, computed in
+/// memory. It can be shown as a diff (a dry run) or written to disk.
+///
+/// Edits are applied to what rust-analyzer computed them against: the synced
+/// buffer of an open document, otherwise the file on disk. Edits for a
+/// specific document version fail if the buffer has changed since.
+#[derive(Debug, Default)]
+pub struct EditPlan {
+    root: PathBuf,
+    documents: BTreeMap<PathBuf, TextDocumentItem>,
+    files: BTreeMap<PathBuf, FileState>,
+    /// `(from, to)` for every file rename, in order
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// Renamed directories, removed once the files in them are moved
+    moved_dirs: Vec<PathBuf>,
+}
+
+impl EditPlan {
+    /// Applies `edit` in memory to the files below `root`, taking the contents
+    /// of the open `documents` from their buffers
+    pub fn new(root: &Path, edit: &WorkspaceEdit, documents: &[TextDocumentItem]) -> Result<Self> {
+        let mut plan = Self {
+            root: root.to_path_buf(),
+            documents: documents
+                .iter()
+                .filter_map(|document| Some((document.uri.to_file_path().ok()?, document.clone())))
+                .collect(),
+            ..Self::default()
+        };
+        for (uri, edits) in edit.changes.iter().flatten() {
+            plan.edit(uri, None, edits)?;
+        }
+        match &edit.document_changes {
+            Some(DocumentChanges::Edits(edits)) => {
+                for edit in edits {
+                    let document = &edit.text_document;
+                    plan.edit(&document.uri, document.version, &text_edits(&edit.edits))?;
+                }
+            }
+            Some(DocumentChanges::Operations(operations)) => {
+                for operation in operations {
+                    match operation {
+                        DocumentChangeOperation::Edit(edit) => {
+                            let document = &edit.text_document;
+                            plan.edit(&document.uri, document.version, &text_edits(&edit.edits))?
+                        }
+                        DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                            let path = plan.path(&create.uri)?;
+                            let options = create.options.as_ref();
+                            let exists = plan.state(&path)?.new.is_some();
+                            if exists && options.and_then(|o| o.ignore_if_exists) == Some(true) {
+                                continue;
+                            }
+                            if exists && options.and_then(|o| o.overwrite) != Some(true) {
+                                anyhow::bail!("{} already exists", path.display());
+                            }
+                            plan.state(&path)?.new = Some(String::new());
+                        }
+                        DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                            let from = plan.path(&rename.old_uri)?;
+                            let to = plan.path(&rename.new_uri)?;
+                            let options = rename.options.as_ref();
+                            if !from.is_dir() {
+                                plan.rename(from, to, options)?;
+                                continue;
+                            }
+                            // A module directory: every file in it moves along
+                            for file in plan.files_below(&from)? {
+                                let moved = to.join(file.strip_prefix(&from)?);
+                                plan.rename(file, moved, options)?;
+                            }
+                            plan.moved_dirs.push(from);
+                        }
+                        DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+                            let path = plan.path(&delete.uri)?;
+                            plan.state(&path)?.new = None;
+                        }
+                    }
+                }
+            }
+            None => (),
+        }
+        Ok(plan)
+    }
+
+    /// The absolute path of a file URI, which has to be inside the project,
+    /// also after following symlinks
+    fn path(&self, uri: &Url) -> Result<PathBuf> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("{uri} is not a file"))?;
+        let root = dunce::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        if !path.starts_with(&self.root) || !canonicalize_existing(&path).starts_with(root) {
+            anyhow::bail!(
+                "Refusing to edit {}, which is outside of the project",
+                path.display()
+            );
+        }
+        Ok(path)
+    }
+
+    /// The state of a file, read the first time it is touched
+    fn state(&mut self, path: &Path) -> Result<&mut FileState> {
+        if !self.files.contains_key(path) {
+            let state = match self.documents.get(path) {
+                Some(document) => FileState {
+                    original: Some(document.text.clone()),
+                    new: Some(document.text.clone()),
+                    version: Some(document.version),
+                    disk: read_file(path)?,
+                },
+                None => {
+                    let original = read_file(path)?;
+                    FileState {
+                        new: original.clone(),
+                        disk: original.clone(),
+                        original,
+                        version: None,
+                    }
+                }
+            };
+            self.files.insert(path.to_path_buf(), state);
+        }
+        Ok(self.files.get_mut(path).expect("inserted above"))
+    }
+
+    /// Applies text edits to a file. With a `version`, the edits were computed
+    /// against that version of the open document.
+    fn edit(&mut self, uri: &Url, version: Option<i32>, edits: &[TextEdit]) -> Result<()> {
+        let path = self.path(uri)?;
+        let state = self.state(&path)?;
+        if let Some(version) = version {
+            match state.version {
+                Some(open) if open == version => (),
+                Some(open) => anyhow::bail!(
+                    "{} changed since the edit was computed (version {version}, now {open}). Please try again",
+                    path.display()
+                ),
+                None => anyhow::bail!(
+                    "{} was closed since the edit was computed. Please try again",
+                    path.display()
+                ),
+            }
+        }
+        let content = state
+            .new
+            .as_deref()
+            .with_context(|| format!("Cannot edit {}, it doesn't exist", path.display()))?;
+        let edited = apply_text_edits(content, edits)
+            .with_context(|| format!("Failed to edit {}", path.display()))?;
+        state.new = Some(edited);
+        Ok(())
+    }
+
+    /// Moves the contents of a file to another path
+    fn rename(
+        &mut self,
+        from: PathBuf,
+        to: PathBuf,
+        options: Option<&RenameFileOptions>,
+    ) -> Result<()> {
+        if self.state(&to)?.new.is_some() {
+            if options.and_then(|o| o.ignore_if_exists) == Some(true) {
+                return Ok(());
+            }
+            if options.and_then(|o| o.overwrite) != Some(true) {
+                anyhow::bail!("{} already exists", to.display());
+            }
+        }
+        let content = self.state(&from)?.new.take().with_context(|| {
+            format!("Cannot rename {}, it doesn't exist", from.display())
+        })?;
+        self.state(&to)?.new = Some(content);
+        self.renames.push((from, to));
+        Ok(())
+    }
+
+    /// The files in a directory and its subdirectories after the edits so
+    /// far: the ones on disk and the ones created by the edit
+    fn files_below(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = BTreeSet::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let entries = fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read {}", dir.display()))?;
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    dirs.push(entry.path());
+                } else {
+                    files.insert(entry.path());
+                }
+            }
+        }
+        files.extend(
+            self.files
+                .keys()
+                .filter(|path| path.starts_with(dir))
+                .cloned(),
+        );
+        files.retain(|path| self.files.get(path).is_none_or(|state| state.new.is_some()));
+        Ok(files.into_iter().collect())
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    /// The files that end up different, relative to the project root
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, state)| state.original != state.new)
+            .map(|(path, _)| self.relative(path).to_path_buf())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.values().all(|state| state.original == state.new)
+    }
+
+    /// What happens to each changed file, by absolute path
+    pub fn file_changes(&self) -> Vec<FileChange<'_>> {
+        self.files
+            .iter()
+            .filter(|(_, state)| state.original != state.new)
+            .map(|(path, state)| FileChange {
+                path,
+                typ: match (&state.original, &state.new) {
+                    (None, _) => FileChangeType::CREATED,
+                    (_, None) => FileChangeType::DELETED,
+                    _ => FileChangeType::CHANGED,
+                },
+                content: state.new.as_deref(),
+            })
+            .collect()
+    }
+
+    /// A unified diff per changed file. Renamed files are diffed against
+    /// their original path.
+    pub fn diff(&self) -> String {
+        let renamed_from: BTreeMap<&Path, &Path> = self
+            .renames
+            .iter()
+            .map(|(from, to)| (to.as_path(), from.as_path()))
+            .collect();
+        let renamed_to: BTreeMap<&Path, &Path> = self
+            .renames
+            .iter()
+            .map(|(from, to)| (from.as_path(), to.as_path()))
+            .collect();
+
+        let mut diffs = Vec::new();
+        for (path, state) in &self.files {
+            if let Some(from) = renamed_from.get(path.as_path()) {
+                let original = self.files[*from].original.as_deref().unwrap_or_default();
+                let header = format!(
+                    "rename from {}\nrename to {}\n",
+                    self.relative(from).display(),
+                    self.relative(path).display()
+                );
+                diffs.push(header + &unified_diff(
+                    &self.relative(from).display().to_string(),
+                    &self.relative(path).display().to_string(),
+                    original,
+                    state.new.as_deref().unwrap_or_default(),
+                ));
+                continue;
+            }
+            if renamed_to.contains_key(path.as_path()) && state.new.is_none() {
+                // Shown at its new path
+                continue;
+            }
+            if state.original == state.new {
+                continue;
+            }
+            let name = self.relative(path).display().to_string();
+            diffs.push(unified_diff(
+                if state.original.is_some() { &name } else { "/dev/null" },
+                if state.new.is_some() { &name } else { "/dev/null" },
+                state.original.as_deref().unwrap_or_default(),
+                state.new.as_deref().unwrap_or_default(),
+            ));
+        }
+        diffs.join("\n")
+    }
+
+    /// Writes all changes to disk. New contents are written to temporary
+    /// files first and only moved into place once all of them were written.
+    /// If moving a file into place fails, the files written so far get their
+    /// previous contents back. Fails if a file changed on disk since the plan
+    /// was made, also if it's open, as that change would be overwritten.
+    pub fn apply(&self) -> Result<()> {
+        for (path, state) in self.changed() {
+            if read_file(path)? != state.disk {
+                anyhow::bail!(
+                    "{} changed on disk since the edit was computed. Please try again",
+                    path.display()
+                );
+            }
+        }
+        self.write()
+    }
+
+    fn changed(&self) -> impl Iterator<Item = (&PathBuf, &FileState)> {
+        self.files
+            .iter()
+            .filter(|(_, state)| state.original != state.new)
+    }
+
+    fn write(&self) -> Result<()> {
+        let mut staged = Vec::new();
+        for (path, state) in self.changed() {
+            let Some(content) = &state.new else {
+                continue;
+            };
+            let result = (|| {
+                let dir = path.parent().context("File without a directory")?;
+                fs::create_dir_all(dir)?;
+                let name = path.file_name().context("File without a name")?;
+                let temporary = dir.join(format!(".{}.edit-tmp", name.to_string_lossy()));
+                fs::write(&temporary, content)?;
+                anyhow::Ok(temporary)
+            })();
+            match result {
+                Ok(temporary) => staged.push((temporary, path.as_path(), state)),
+                Err(e) => {
+                    for (temporary, ..) in staged {
+                        let _ = fs::remove_file(temporary);
+                    }
+                    return Err(e).with_context(|| format!("Failed to write {}", path.display()));
+                }
+            }
+        }
+
+        let mut written = Vec::new();
+        let mut staged = staged.into_iter();
+        while let Some((temporary, path, state)) = staged.next() {
+            if let Err(e) = fs::rename(&temporary, path) {
+                let _ = fs::remove_file(&temporary);
+                for (temporary, ..) in staged {
+                    let _ = fs::remove_file(temporary);
+                }
+                let restored = restore(&written);
+                return Err(e).with_context(|| {
+                    format!("Failed to replace {}. {restored}", path.display())
+                });
+            }
+            written.push((path, state));
+        }
+        for (path, state) in self.changed() {
+            if state.new.is_none() && state.original.is_some() {
+                if let Err(e) = fs::remove_file(path) {
+                    let restored = restore(&written);
+                    return Err(e).with_context(|| {
+                        format!("Failed to delete {}. {restored}", path.display())
+                    });
+                }
+                written.push((path, state));
+            }
+        }
+        for dir in &self.moved_dirs {
+            remove_empty_dirs(dir);
+        }
+        Ok(())
+    }
+}
+
+/// Puts back the contents the files changed by `EditPlan::write` had on disk
+/// and describes the outcome for the error message
+fn restore(written: &[(&Path, &FileState)]) -> String {
+    let mut restored = Vec::new();
+    let mut failed = Vec::new();
+    for (path, state) in written.iter().rev() {
+        let result = match &state.disk {
+            Some(content) => fs::write(path, content),
+            None => fs::remove_file(path),
+        };
+        match result {
+            Ok(()) => restored.push(path.display().to_string()),
+            Err(e) => failed.push(format!("{} ({e})", path.display())),
+        }
+    }
+    let mut outcome = Vec::new();
+    if !restored.is_empty() {
+        outcome.push(format!("Restored {}", restored.join(", ")));
+    }
+    if !failed.is_empty() {
+        outcome.push(format!("Failed to restore {}", failed.join(", ")));
+    }
+    if outcome.is_empty() {
+        return "No files were changed".to_string();
+    }
+    outcome.join(". ")
+}
+
+/// Drops the parts of `edit` that touch files `keep` rejects. File
+/// operations are kept only if all files they touch are kept.
+pub fn retain_files(edit: &mut WorkspaceEdit, keep: impl Fn(&Url) -> bool) {
+    if let Some(changes) = &mut edit.changes {
+        changes.retain(|uri, _| keep(uri));
+    }
+    match &mut edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.retain(|edit| keep(&edit.text_document.uri)),
+        Some(DocumentChanges::Operations(operations)) => {
+            operations.retain(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => keep(&edit.text_document.uri),
+                DocumentChangeOperation::Op(ResourceOp::Create(create)) => keep(&create.uri),
+                DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                    keep(&rename.old_uri) && keep(&rename.new_uri)
+                }
+                DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => keep(&delete.uri),
+            })
+        }
+        None => (),
+    }
+}
+
+/// Every document `edit` changes, creates, renames or deletes
+pub fn edited_uris(edit: &WorkspaceEdit) -> BTreeSet<Url> {
+    let mut uris: BTreeSet<Url> = edit
+        .changes
+        .iter()
+        .flatten()
+        .map(|(uri, _)| uri.clone())
+        .collect();
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => {
+            uris.extend(edits.iter().map(|edit| edit.text_document.uri.clone()))
+        }
+        Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+                match operation {
+                    DocumentChangeOperation::Edit(edit) => {
+                        uris.insert(edit.text_document.uri.clone());
+                    }
+                    DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                        uris.insert(create.uri.clone());
+                    }
+                    DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                        uris.insert(rename.old_uri.clone());
+                        uris.insert(rename.new_uri.clone());
+                    }
+                    DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+                        uris.insert(delete.uri.clone());
+                    }
+                }
+            }
+        }
+        None => (),
+    }
+    uris
+}
+
+/// Removes a directory if only empty directories are left in it
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|typ| typ.is_dir()) {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    // Fails if anything is left, which is kept then
+    let _ = fs::remove_dir(dir);
+}
+
+/// `path` with the symlinks resolved in the part of it that exists
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = dunce::canonicalize(ancestor) {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return canonical.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// The contents of a file, `None` if it doesn't exist
+fn read_file(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn text_edits(edits: &[OneOf<TextEdit, AnnotatedTextEdit>]) -> Vec<TextEdit> {
+    edits
+        .iter()
+        .map(|edit| match edit {
+            OneOf::Left(edit) => edit.clone(),
+            OneOf::Right(annotated) => annotated.text_edit.clone(),
+        })
+        .collect()
+}
+
+/// The byte offset of a position in `content`. Characters past the end of
+/// a line mean its end, as the LSP specifies; lines past the end are an error.
+fn byte_offset(content: &str, position: Position) -> Result<usize> {
+    let lines = content.split_inclusive('\n').count();
+    if position.line as usize > lines {
+        anyhow::bail!(
+            "Position {}:{} is past the end of the file, which has {lines} lines",
+            position.line + 1,
+            position.character + 1
+        );
+    }
+    Ok(position_to_byte_offset(content, position))
+}
+
+/// Whether a file uses `\r\n` line endings, judged by its first line
+fn uses_crlf(content: &str) -> bool {
+    content
+        .find('\n')
+        .is_some_and(|end| content[..end].ends_with('\r'))
+}
+
+/// Applies LSP text edits, whose positions all refer to the original content.
+/// Positions count UTF-16 code units. Inserted text gets the line endings of
+/// the file.
+pub fn apply_text_edits(content: &str, edits: &[TextEdit]) -> Result<String> {
+    let crlf = uses_crlf(content);
+    let mut ranges = Vec::with_capacity(edits.len());
+    for edit in edits {
+        let start = byte_offset(content, edit.range.start)?;
+        let end = byte_offset(content, edit.range.end)?;
+        if end < start {
+            anyhow::bail!(
+                "Edit ends before it starts at {}:{}",
+                edit.range.start.line + 1,
+                edit.range.start.character + 1
+            );
+        }
+        let new_text = if crlf {
+            edit.new_text.replace("\r\n", "\n").replace('\n', "\r\n")
+        } else {
+            edit.new_text.clone()
+        };
+        ranges.push((start, end, new_text, edit.range.start));
+    }
+    // Stable, so inserts at the same position keep their order
+    ranges.sort_by_key(|(start, end, ..)| (*start, *end));
+
+    let mut result = String::with_capacity(content.len());
+    let mut last = 0;
+    for (start, end, new_text, position) in ranges {
+        if start < last {
+            anyhow::bail!(
+                "Overlapping edits at {}:{}",
+                position.line + 1,
+                position.character + 1
+            );
+        }
+        result.push_str(&content[last..start]);
+        result.push_str(&new_text);
+        last = end;
+    }
+    result.push_str(&content[last..]);
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffLine {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// The line by line difference of `old` and `new`, by longest common subsequence
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut lines: Vec<DiffLine> = (0..prefix).map(|i| DiffLine::Equal(i, i)).collect();
+    let (n, m) = (old_middle.len(), new_middle.len());
+    if n * m > MAX_DIFF_CELLS {
+        lines.extend((prefix..prefix + n).map(DiffLine::Delete));
+        lines.extend((prefix..prefix + m).map(DiffLine::Insert));
+    } else {
+        // lengths[i][j]: the longest common subsequence of old[i..] and new[j..]
+        let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i][j] = if old_middle[i] == new_middle[j] {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                lines.push(DiffLine::Equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+                // Deletions first, like diff shows them
+                lines.push(DiffLine::Delete(prefix + i));
+                i += 1;
+            } else {
+                lines.push(DiffLine::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_suffix, new_suffix) = (old.len() - suffix, new.len() - suffix);
+    lines.extend((0..suffix).map(|k| DiffLine::Equal(old_suffix + k, new_suffix + k)));
+    lines
+}
+
+/// A unified diff of two versions of a file, empty if they are the same
+pub fn unified_diff(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let old_header = if old_name == "/dev/null" {
+        old_name.to_string()
+    } else {
+        format!("a/{old_name}")
+    };
+    let new_header = if new_name == "/dev/null" {
+        new_name.to_string()
+    } else {
+        format!("b/{new_name}")
+    };
+    let mut out = format!("--- {old_header}\n+++ {new_header}\n");
+
+    // Group changes that are close enough to share their context
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        let start = change.saturating_sub(DIFF_CONTEXT);
+        let end = (change + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+        let old_count = hunk.iter().filter(|l| !matches!(l, DiffLine::Insert(_))).count();
+        let new_count = hunk.iter().filter(|l| !matches!(l, DiffLine::Delete(_))).count();
+        // The line before the hunk in each file, to number empty ranges like diff does
+        let (old_before, new_before) = lines[..start].iter().fold((0, 0), |(o, n), line| match line {
+            DiffLine::Equal(..) => (o + 1, n + 1),
+            DiffLine::Delete(_) => (o + 1, n),
+            DiffLine::Insert(_) => (o, n + 1),
+        });
+        let old_start = if old_count == 0 { old_before } else { old_before + 1 };
+        let new_start = if new_count == 0 { new_before } else { new_before + 1 };
+        out.push_str(&format!(
+            "@@ -{old_start},{old_count} +{new_start},{new_count} @@\n"
+        ));
+        for line in hunk {
+            match *line {
+                DiffLine::Equal(i, _) => out.push_str(&format!(" {}\n", old_lines[i])),
+                DiffLine::Delete(i) => out.push_str(&format!("-{}\n", old_lines[i])),
+                DiffLine::Insert(j) => out.push_str(&format!("+{}\n", new_lines[j])),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+    use lsp_types::{
+        CreateFile, DeleteFile, OptionalVersionedTextDocumentIdentifier, Range, RenameFile,
+        TextDocumentEdit,
+    };
+    use std::collections::HashMap;
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextEdit {
+        TextEdit::new(
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            text.to_string(),
+        )
+    }
+
+    fn document_edit(uri: &Url, version: Option<i32>, edits: Vec<TextEdit>) -> DocumentChangeOperation {
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version,
+            },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        })
+    }
+
+    fn operations(operations: Vec<DocumentChangeOperation>) -> WorkspaceEdit {
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..WorkspaceEdit::default()
+        }
+    }
+
+    /// A fresh project directory with the given files
+    fn project(name: &str, files: &[(&str, &str)]) -> TestDir {
+        let root = TestDir::new(name);
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    fn uri(root: &Path, path: &str) -> Url {
+        Url::from_file_path(root.join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_apply_text_edits() {
+        let content = "fn old() {}\nfn main() { old(); }\n";
+        // Edits are applied against the original positions, in any order
+        let edits = [edit((1, 12), (1, 15), "new"), edit((0, 3), (0, 6), "new")];
+        assert_eq!(
+            apply_text_edits(content, &edits).unwrap(),
+            "fn new() {}\nfn main() { new(); }\n"
+        );
+        // Inserts at the same position keep their order, and an edit may
+        // start where the previous one ends
+        let edits = [
+            edit((0, 0), (0, 0), "use a;\n"),
+            edit((0, 0), (0, 0), "use b;\n"),
+            edit((0, 3), (0, 6), "x"),
+            edit((0, 6), (0, 6), "_y"),
+        ];
+        assert_eq!(
+            apply_text_edits(content, &edits).unwrap(),
+            "use a;\nuse b;\nfn x_y() {}\nfn main() { old(); }\n"
+        );
+        // The end of the file is the start of the line after the last one
+        assert_eq!(
+            apply_text_edits(content, &[edit((2, 0), (2, 0), "// end\n")]).unwrap(),
+            "fn old() {}\nfn main() { old(); }\n// end\n"
+        );
+        // Characters past the end of a line mean its end
+        assert_eq!(
+            apply_text_edits(content, &[edit((0, 3), (0, 100), "a();")]).unwrap(),
+            "fn a();\nfn main() { old(); }\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_text_edits_errors() {
+        let content = "fn old() {}\n";
+        let overlapping = [edit((0, 0), (0, 5), "a"), edit((0, 3), (0, 6), "b")];
+        assert_eq!(
+            apply_text_edits(content, &overlapping).unwrap_err().to_string(),
+            "Overlapping edits at 1:4"
+        );
+        assert!(apply_text_edits(content, &[edit((0, 5), (0, 3), "")]).is_err());
+        assert!(apply_text_edits(content, &[edit((5, 0), (5, 0), "x")]).is_err());
+    }
+
+    #[test]
+    fn test_apply_text_edits_utf16() {
+        // 🦀 is two UTF-16 code units, é one
+        let content = "let s = \"🦀é\"; let t = 1;\n";
+        let edits = [edit((0, 19), (0, 20), "u"), edit((0, 9), (0, 11), "x")];
+        assert_eq!(
+            apply_text_edits(content, &edits).unwrap(),
+            "let s = \"xé\"; let u = 1;\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_text_edits_crlf() {
+        let content = "fn a() {\r\n    b();\r\n}\r\n";
+        let edits = [edit((1, 4), (1, 8), "c();\n    d();")];
+        assert_eq!(
+            apply_text_edits(content, &edits).unwrap(),
+            "fn a() {\r\n    c();\r\n    d();\r\n}\r\n"
+        );
+        // Text that already uses CRLF isn't doubled
+        let edits = [edit((2, 1), (2, 1), "\r\nfn e() {}")];
+        assert_eq!(
+            apply_text_edits(content, &edits).unwrap(),
+            "fn a() {\r\n    b();\r\n}\r\nfn e() {}\r\n"
+        );
+        // Files with LF keep LF
+        assert_eq!(
+            apply_text_edits("a\nb\n", &[edit((0, 1), (0, 1), "\r\nc")]).unwrap(),
+            "a\r\nc\nb\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_plan_changes() {
+        let root = project(
+            "edit_plan_changes_test",
+            &[
+                ("src/lib.rs", "mod a;\nmod d;\n"),
+                ("src/a.rs", "pub fn a() {}\n"),
+                ("src/d.rs", "pub fn d() {}\n"),
+            ],
+        );
+        let mut changes = HashMap::new();
+        changes.insert(uri(&root, "src/lib.rs"), vec![edit((0, 4), (0, 5), "b")]);
+        let mut edit = operations(vec![
+            DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+                old_uri: uri(&root, "src/a.rs"),
+                new_uri: uri(&root, "src/b.rs"),
+                options: None,
+                annotation_id: None,
+            })),
+            document_edit(&uri(&root, "src/b.rs"), None, vec![edit((0, 7), (0, 8), "b")]),
+            DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                uri: uri(&root, "src/c.rs"),
+                options: None,
+                annotation_id: None,
+            })),
+            document_edit(&uri(&root, "src/c.rs"), None, vec![edit((0, 0), (0, 0), "pub fn c() {}\n")]),
+            DocumentChangeOperation::Op(ResourceOp::Delete(DeleteFile {
+                uri: uri(&root, "src/d.rs"),
+                options: None,
+            })),
+        ]);
+        edit.changes = Some(changes);
+
+        let plan = EditPlan::new(&root, &edit, &[]).unwrap();
+        assert_eq!(
+            plan.changed_files(),
+            ["src/a.rs", "src/b.rs", "src/c.rs", "src/d.rs", "src/lib.rs"].map(PathBuf::from)
+        );
+        let changes: Vec<_> = plan
+            .file_changes()
+            .into_iter()
+            .map(|change| (change.typ, change.content))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (FileChangeType::DELETED, None),
+                (FileChangeType::CREATED, Some("pub fn b() {}\n")),
+                (FileChangeType::CREATED, Some("pub fn c() {}\n")),
+                (FileChangeType::DELETED, None),
+                (FileChangeType::CHANGED, Some("mod b;\nmod d;\n")),
+            ]
+        );
+        assert_eq!(
+            plan.diff(),
+            "rename from src/a.rs\nrename to src/b.rs\n--- a/src/a.rs\n+++ b/src/b.rs\n@@ -1,1 +1,1 @@\n-pub fn a() {}\n+pub fn b() {}\n\n\
+             --- /dev/null\n+++ b/src/c.rs\n@@ -0,0 +1,1 @@\n+pub fn c() {}\n\n\
+             --- a/src/d.rs\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-pub fn d() {}\n\n\
+             --- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n-mod a;\n+mod b;\n mod d;\n"
+        );
+        // A dry run doesn't touch the files
+        assert!(root.join("src/a.rs").exists());
+
+        plan.apply().unwrap();
+        assert!(!root.join("src/a.rs").exists());
+        assert!(!root.join("src/d.rs").exists());
+        assert_eq!(fs::read_to_string(root.join("src/b.rs")).unwrap(), "pub fn b() {}\n");
+        assert_eq!(fs::read_to_string(root.join("src/c.rs")).unwrap(), "pub fn c() {}\n");
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "mod b;\nmod d;\n");
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(root.join("src")).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_edit_plan_rename_directory() {
+        let root = project(
+            "edit_plan_rename_directory_test",
+            &[
+                ("src/lib.rs", "mod foo;\n"),
+                ("src/foo/mod.rs", "mod bar;\n"),
+                ("src/foo/bar.rs", "fn b() {}\n"),
+            ],
+        );
+        let workspace_edit = operations(vec![
+            document_edit(&uri(&root, "src/lib.rs"), None, vec![edit((0, 4), (0, 7), "baz")]),
+            DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+                old_uri: uri(&root, "src/foo"),
+                new_uri: uri(&root, "src/baz"),
+                options: None,
+                annotation_id: None,
+            })),
+        ]);
+        let plan = EditPlan::new(&root, &workspace_edit, &[]).unwrap();
+        assert_eq!(
+            plan.changed_files(),
+            ["src/baz/bar.rs", "src/baz/mod.rs", "src/foo/bar.rs", "src/foo/mod.rs", "src/lib.rs"]
+                .map(PathBuf::from)
+        );
+        let diff = plan.diff();
+        assert!(diff.contains("rename from src/foo/mod.rs\nrename to src/baz/mod.rs\n"), "{diff}");
+        assert!(diff.contains("rename from src/foo/bar.rs\nrename to src/baz/bar.rs\n"), "{diff}");
+        let deleted: Vec<_> = plan
+            .file_changes()
+            .into_iter()
+            .filter(|change| change.typ == FileChangeType::DELETED)
+            .map(|change| change.path.to_path_buf())
+            .collect();
+        assert_eq!(deleted, vec![root.join("src/foo/bar.rs"), root.join("src/foo/mod.rs")]);
+
+        plan.apply().unwrap();
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "mod baz;\n");
+        assert_eq!(fs::read_to_string(root.join("src/baz/mod.rs")).unwrap(), "mod bar;\n");
+        assert_eq!(fs::read_to_string(root.join("src/baz/bar.rs")).unwrap(), "fn b() {}\n");
+        assert!(!root.join("src/foo").exists());
+    }
+
+    #[test]
+    fn test_edit_plan_resource_options() {
+        let root = project(
+            "edit_plan_options_test",
+            &[("src/lib.rs", "fn a() {}\n"), ("src/b.rs", "")],
+        );
+        let create = |overwrite, ignore_if_exists| {
+            operations(vec![DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                uri: uri(&root, "src/lib.rs"),
+                options: Some(lsp_types::CreateFileOptions {
+                    overwrite: Some(overwrite),
+                    ignore_if_exists: Some(ignore_if_exists),
+                }),
+                annotation_id: None,
+            }))])
+        };
+        assert!(EditPlan::new(&root, &create(false, false), &[]).is_err());
+        assert!(EditPlan::new(&root, &create(false, true), &[]).unwrap().is_empty());
+        let plan = EditPlan::new(&root, &create(true, false), &[]).unwrap();
+        assert_eq!(plan.file_changes()[0].content, Some(""));
+
+        let rename = operations(vec![DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+            old_uri: uri(&root, "src/lib.rs"),
+            new_uri: uri(&root, "src/b.rs"),
+            options: None,
+            annotation_id: None,
+        }))]);
+        assert!(EditPlan::new(&root, &rename, &[]).is_err());
+        let missing = operations(vec![document_edit(
+            &uri(&root, "src/missing.rs"),
+            None,
+            vec![edit((0, 0), (0, 0), "x")],
+        )]);
+        assert!(EditPlan::new(&root, &missing, &[]).is_err());
+    }
+
+    #[test]
+    fn test_edit_plan_outside_project() {
+        let root = project("edit_plan_outside_test", &[("src/lib.rs", "")]);
+        let mut changes = HashMap::new();
+        changes.insert(
+            Url::from_file_path(root.parent().unwrap().join("other.rs")).unwrap(),
+            vec![edit((0, 0), (0, 0), "x")],
+        );
+        let edit = WorkspaceEdit::new(changes);
+        let error = EditPlan::new(&root, &edit, &[]).unwrap_err();
+        assert!(error.to_string().starts_with("Refusing to edit"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_plan_symlink_outside_project() {
+        let root = project("edit_plan_symlink_test", &[("src/lib.rs", "")]);
+        let outside = project("edit_plan_symlink_target", &[("other.rs", "")]);
+        std::os::unix::fs::symlink(&outside, root.join("src/linked")).unwrap();
+        for path in ["src/linked/other.rs", "src/linked/new.rs"] {
+            let mut changes = HashMap::new();
+            changes.insert(uri(&root, path), vec![edit((0, 0), (0, 0), "x")]);
+            let error = EditPlan::new(&root, &WorkspaceEdit::new(changes), &[]).unwrap_err();
+            assert!(error.to_string().starts_with("Refusing to edit"), "{error}");
+        }
+        assert_eq!(fs::read_to_string(outside.join("other.rs")).unwrap(), "");
+    }
+
+    #[test]
+    fn test_edit_plan_open_documents() {
+        let root = project("edit_plan_documents_test", &[("src/lib.rs", "fn a() {}\n")]);
+        let lib = uri(&root, "src/lib.rs");
+        // The buffer has unsaved changes rust-analyzer computed the edit against
+        let documents = [TextDocumentItem::new(
+            lib.clone(),
+            "rust".into(),
+            3,
+            "fn a() {}\nfn b() { a() }\n".into(),
+        )];
+        let rename = |version| {
+            operations(vec![document_edit(
+                &lib,
+                version,
+                vec![edit((0, 3), (0, 4), "c"), edit((1, 9), (1, 10), "c")],
+            )])
+        };
+
+        let plan = EditPlan::new(&root, &rename(Some(3)), &documents).unwrap();
+        assert_eq!(
+            plan.file_changes()[0].content,
+            Some("fn c() {}\nfn b() { c() }\n")
+        );
+        let error = EditPlan::new(&root, &rename(Some(2)), &documents).unwrap_err();
+        assert!(error.to_string().contains("version 2, now 3"), "{error}");
+        assert!(EditPlan::new(&root, &rename(Some(3)), &[]).is_err());
+        // Unversioned edits still apply to the buffer
+        assert!(EditPlan::new(&root, &rename(None), &documents).is_ok());
+
+        // Applying writes the buffer with the edit, although it differs from the disk
+        plan.apply().unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "fn c() {}\nfn b() { c() }\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_plan_stale_file() {
+        let root = project("edit_plan_stale_test", &[("src/lib.rs", "fn a() {}\n")]);
+        let mut changes = HashMap::new();
+        changes.insert(uri(&root, "src/lib.rs"), vec![edit((0, 3), (0, 4), "b")]);
+        let plan = EditPlan::new(&root, &WorkspaceEdit::new(changes.clone()), &[]).unwrap();
+
+        fs::write(root.join("src/lib.rs"), "fn x() {}\n").unwrap();
+        assert!(plan.apply().is_err());
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "fn x() {}\n");
+
+        // Also for a file taken from its synced buffer
+        let documents = [TextDocumentItem::new(
+            uri(&root, "src/lib.rs"),
+            "rust".into(),
+            1,
+            "fn a() {}\n".into(),
+        )];
+        let plan = EditPlan::new(&root, &WorkspaceEdit::new(changes), &documents).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn y() {}\n").unwrap();
+        let error = plan.apply().unwrap_err();
+        assert!(error.to_string().contains("changed on disk"), "{error}");
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "fn y() {}\n");
+    }
+
+    #[test]
+    fn test_edit_plan_rollback() {
+        let root = project("edit_plan_rollback_test", &[("src/a.rs", "a\n")]);
+        let workspace_edit = operations(vec![
+            document_edit(&uri(&root, "src/a.rs"), None, vec![edit((0, 0), (0, 1), "b")]),
+            DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                uri: uri(&root, "src/c.rs"),
+                options: None,
+                annotation_id: None,
+            })),
+            document_edit(&uri(&root, "src/c.rs"), None, vec![edit((0, 0), (0, 0), "c")]),
+        ]);
+        let plan = EditPlan::new(&root, &workspace_edit, &[]).unwrap();
+
+        // Replacing a directory fails after `src/a.rs` is in place
+        fs::create_dir_all(root.join("src/c.rs/inner")).unwrap();
+        let error = format!("{:#}", plan.write().unwrap_err());
+        assert!(error.contains("Failed to replace"), "{error}");
+        assert!(error.contains("Restored"), "{error}");
+        assert!(error.contains("a.rs"), "{error}");
+        assert_eq!(fs::read_to_string(root.join("src/a.rs")).unwrap(), "a\n");
+        let mut files: Vec<_> = fs::read_dir(root.join("src"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["a.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn test_retain_files() {
+        let (a, b) = (
+            Url::parse("file:///project/src/a.rs").unwrap(),
+            Url::parse("file:///project/src/b.rs").unwrap(),
+        );
+        let mut workspace_edit = operations(vec![
+            document_edit(&a, None, vec![edit((0, 0), (0, 1), "x")]),
+            document_edit(&b, None, vec![edit((0, 0), (0, 1), "y")]),
+            DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+                old_uri: a.clone(),
+                new_uri: b.clone(),
+                options: None,
+                annotation_id: None,
+            })),
+        ]);
+        retain_files(&mut workspace_edit, |uri| uri == &a);
+        let Some(DocumentChanges::Operations(operations)) = workspace_edit.document_changes else {
+            panic!("expected operations");
+        };
+        assert_eq!(operations, vec![document_edit(&a, None, vec![edit((0, 0), (0, 1), "x")])]);
+    }
+
+    #[test]
+    fn test_edited_uris() {
+        let (a, b, c) = (
+            Url::parse("file:///project/src/a.rs").unwrap(),
+            Url::parse("file:///project/src/b.rs").unwrap(),
+            Url::parse("file:///project/src/c.rs").unwrap(),
+        );
+        let workspace_edit = operations(vec![
+            document_edit(&c, Some(1), vec![edit((0, 0), (0, 1), "x")]),
+            DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+                old_uri: a.clone(),
+                new_uri: b.clone(),
+                options: None,
+                annotation_id: None,
+            })),
+        ]);
+        assert_eq!(
+            edited_uris(&workspace_edit).into_iter().collect::<Vec<_>>(),
+            vec![a, b, c]
+        );
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff("lib.rs", "lib.rs", old, new),
+            "--- a/lib.rs\n+++ b/lib.rs\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(unified_diff("lib.rs", "lib.rs", old, old), "");
+        assert_eq!(
+            unified_diff("/dev/null", "new.rs", "", "x\n"),
+            "--- /dev/null\n+++ b/new.rs\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+        // Changes close to each other share a hunk
+        assert_eq!(
+            unified_diff("lib.rs", "lib.rs", "a\nb\nc\nd\n", "A\nb\nc\nD\n"),
+            "--- a/lib.rs\n+++ b/lib.rs\n@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n"
+        );
+    }
+}
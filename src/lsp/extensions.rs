@@ -0,0 +1,290 @@
+//! Requests rust-analyzer supports beyond the LSP
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use lsp_types::request::Request;
+use lsp_types::{
+    LocationLink, Position, Range, TextDocumentIdentifier, TextDocumentPositionParams,
+    WorkspaceEdit,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsrParams {
+    pub query: String,
+    pub parse_only: bool,
+    /// Where paths in the query are resolved
+    #[serde(flatten)]
+    pub position: TextDocumentPositionParams,
+    /// Restricts the search to these ranges of the position's file.
+    /// Empty searches the whole workspace.
+    pub selections: Vec<Range>,
+}
+
+/// Structural search and replace, e.g. `foo($a, $b) ==>> bar($b, $a)`
+pub enum Ssr {}
+
+impl Request for Ssr {
+    type Params = SsrParams;
+    type Result = WorkspaceEdit;
+    const METHOD: &'static str = "experimental/ssr";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandMacroParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpandedMacro {
+    pub name: String,
+    /// The recursively expanded, pretty-printed code
+    pub expansion: String,
+}
+
+/// Expands the macro call or attribute (including derives) at a position
+pub enum ExpandMacro {}
+
+impl Request for ExpandMacro {
+    type Params = ExpandMacroParams;
+    type Result = Option<ExpandedMacro>;
+    const METHOD: &'static str = "rust-analyzer/expandMacro";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnablesParams {
+    pub text_document: TextDocumentIdentifier,
+    /// Only the runnables around this position, or all of the file
+    pub position: Option<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Runnable {
+    /// E.g. `test tests::parses` or `run binary`
+    pub label: String,
+    pub location: Option<LocationLink>,
+    pub kind: RunnableKind,
+    pub args: RunnableArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunnableKind {
+    Cargo,
+    Shell,
+}
+
+/// The arguments of both cargo and shell runnables, which differ in which
+/// fields they set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnableArgs {
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    pub cwd: Option<PathBuf>,
+    pub workspace_root: Option<PathBuf>,
+    /// A replacement for the `cargo` command
+    pub override_cargo: Option<String>,
+    /// The arguments before `--`, starting with the subcommand
+    #[serde(default)]
+    pub cargo_args: Vec<String>,
+    /// Set by older rust-analyzer versions, appended to `cargo_args`
+    #[serde(default)]
+    pub cargo_extra_args: Vec<String>,
+    /// The arguments after `--`, for the test binary
+    #[serde(default)]
+    pub executable_args: Vec<String>,
+    /// The program of a shell runnable
+    pub program: Option<String>,
+    /// The arguments of a shell runnable
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl RunnableArgs {
+    /// The arguments of the cargo invocation, without the `cargo` itself
+    pub fn cargo_command(&self) -> Vec<String> {
+        let mut command: Vec<String> = self
+            .cargo_args
+            .iter()
+            .chain(&self.cargo_extra_args)
+            .cloned()
+            .collect();
+        if !self.executable_args.is_empty() {
+            command.push("--".to_string());
+            command.extend(self.executable_args.iter().cloned());
+        }
+        command
+    }
+}
+
+impl Runnable {
+    /// The command line as a shell would show it
+    pub fn command_line(&self) -> String {
+        let (program, args) = match self.kind {
+            RunnableKind::Cargo => (
+                self.args.override_cargo.as_deref().unwrap_or("cargo"),
+                self.args.cargo_command(),
+            ),
+            RunnableKind::Shell => (
+                self.args.program.as_deref().unwrap_or_default(),
+                self.args.args.clone(),
+            ),
+        };
+        std::iter::once(program.to_string())
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The tests, benches, binaries and doctests of a file, with their exact
+/// cargo invocations
+pub enum Runnables {}
+
+impl Request for Runnables {
+    type Params = RunnablesParams;
+    type Result = Vec<Runnable>;
+    const METHOD: &'static str = "experimental/runnables";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TestInfo {
+    pub runnable: Runnable,
+}
+
+/// The tests that call the function at a position, directly or indirectly
+pub enum RelatedTests {}
+
+impl Request for RelatedTests {
+    type Params = TextDocumentPositionParams;
+    type Result = Vec<TestInfo>;
+    const METHOD: &'static str = "rust-analyzer/relatedTests";
+}
+
+/// The HIR of the function at a position, as text
+pub enum ViewHir {}
+
+impl Request for ViewHir {
+    type Params = TextDocumentPositionParams;
+    type Result = String;
+    const METHOD: &'static str = "rust-analyzer/viewHir";
+}
+
+/// The MIR of the function at a position, as text
+pub enum ViewMir {}
+
+impl Request for ViewMir {
+    type Params = TextDocumentPositionParams;
+    type Result = String;
+    const METHOD: &'static str = "rust-analyzer/viewMir";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewSyntaxTreeParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// The syntax tree of a file. Older rust-analyzer versions return it as
+/// indented text, newer ones as JSON.
+pub enum ViewSyntaxTree {}
+
+impl Request for ViewSyntaxTree {
+    type Params = ViewSyntaxTreeParams;
+    type Result = String;
+    const METHOD: &'static str = "rust-analyzer/viewSyntaxTree";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryLayoutNode {
+    /// The field name, or the type name for the root
+    pub item_name: String,
+    pub typename: String,
+    pub size: u64,
+    /// The offset within the parent
+    pub offset: u64,
+    pub alignment: u64,
+    /// -1 for the root
+    pub parent_idx: i64,
+    /// -1 without children
+    pub children_start: i64,
+    pub children_len: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecursiveMemoryLayout {
+    /// The type itself first, the children of each node next to each other
+    pub nodes: Vec<MemoryLayoutNode>,
+}
+
+/// The layout of the type at a position and of its fields, recursively
+pub enum ViewRecursiveMemoryLayout {}
+
+impl Request for ViewRecursiveMemoryLayout {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<RecursiveMemoryLayout>;
+    const METHOD: &'static str = "rust-analyzer/viewRecursiveMemoryLayout";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ViewCrateGraphParams {
+    /// Include the dependencies, not only the workspace crates
+    pub full: bool,
+}
+
+/// The crate graph rust-analyzer works with, in GraphViz DOT format
+pub enum ViewCrateGraph {}
+
+impl Request for ViewCrateGraph {
+    type Params = ViewCrateGraphParams;
+    type Result = String;
+    const METHOD: &'static str = "rust-analyzer/viewCrateGraph";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_runnable_command_line() {
+        let runnable: Runnable = serde_json::from_value(json!({
+            "label": "test tests::parses",
+            "location": {
+                "targetUri": "file:///project/src/lib.rs",
+                "targetRange": { "start": { "line": 10, "character": 4 }, "end": { "line": 14, "character": 5 } },
+                "targetSelectionRange": { "start": { "line": 11, "character": 7 }, "end": { "line": 11, "character": 13 } }
+            },
+            "kind": "cargo",
+            "args": {
+                "environment": { "RUST_BACKTRACE": "short" },
+                "cwd": "/project",
+                "workspaceRoot": "/project",
+                "cargoArgs": ["test", "--package", "parser", "--lib"],
+                "executableArgs": ["tests::parses", "--exact", "--show-output"]
+            }
+        }))
+        .unwrap();
+        assert_eq!(runnable.kind, RunnableKind::Cargo);
+        assert_eq!(
+            runnable.command_line(),
+            "cargo test --package parser --lib -- tests::parses --exact --show-output"
+        );
+
+        let shell: Runnable = serde_json::from_value(json!({
+            "label": "run main",
+            "kind": "shell",
+            "args": { "cwd": "/project", "program": "buck", "args": ["run", "//main"] }
+        }))
+        .unwrap();
+        assert_eq!(shell.command_line(), "buck run //main");
+    }
+}
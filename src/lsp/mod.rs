@@ -1,12 +1,19 @@
 mod change_notifier;
 mod client_state;
+mod documents;
+mod edits;
+mod extensions;
 mod rust_analyzer_lsp;
+mod supervisor;
 mod utils;
 
 pub(super) struct Stop;
 
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
 
+pub use edits::retain_files;
+pub use extensions::{MemoryLayoutNode, RecursiveMemoryLayout, Runnable, RunnableKind};
 pub use rust_analyzer_lsp::RustAnalyzerLsp;
 pub use utils::*;
 
@@ -15,6 +22,46 @@ pub enum LspNotification {
     Indexing { project: PathBuf, is_indexing: bool },
     IndexingProgress(IndexingProgress),
     IndexingPauseResume { project: PathBuf, should_pause: bool },
+    ServerHealth(ServerHealth),
+}
+
+/// The state of the rust-analyzer process of a project
+#[derive(Debug, Clone)]
+pub struct ServerHealth {
+    pub project: PathBuf,
+    /// False while rust-analyzer is being restarted
+    pub is_running: bool,
+    /// How often rust-analyzer was restarted after crashing or hanging
+    pub restart_count: usize,
+    /// Why rust-analyzer was last restarted, or why restarting it failed
+    pub last_error: Option<String>,
+}
+
+impl ServerHealth {
+    pub fn status_message(&self) -> String {
+        let state = if self.is_running {
+            format!("rust-analyzer running ({} restarts)", self.restart_count)
+        } else {
+            format!("rust-analyzer restarting ({} restarts)", self.restart_count)
+        };
+        match &self.last_error {
+            Some(error) => format!("{state}, last error: {error}"),
+            None => state,
+        }
+    }
+}
+
+/// The indexing progress of a project, updated by the LSP client and by pausing
+pub(super) type SharedProgress = Arc<Mutex<IndexingProgress>>;
+
+/// Runs `f` on the shared progress and returns a snapshot to send to the UI
+pub(super) fn update_progress(
+    progress: &SharedProgress,
+    f: impl FnOnce(&mut IndexingProgress),
+) -> IndexingProgress {
+    let mut progress = progress.lock().unwrap_or_else(PoisonError::into_inner);
+    f(&mut progress);
+    progress.clone()
 }
 
 /// Tracks detailed indexing progress information
@@ -74,34 +121,37 @@ impl IndexingProgress {
     
     /// Marks the start of indexing
     pub fn start_indexing(&mut self) {
+        let now = chrono::Utc::now();
         self.is_indexing = true;
-        self.is_paused = false;
-        self.started_at = Some(chrono::Utc::now());
+        self.started_at = Some(now);
         self.completed_at = None;
-        self.paused_at = None;
         self.total_paused_time = 0;
+        // A pause outlasts an indexing run, and counts from the start of the next one
+        self.paused_at = self.is_paused.then_some(now);
     }
     
     /// Marks the completion of indexing
     pub fn complete_indexing(&mut self) {
+        let now = chrono::Utc::now();
+        if let Some(paused_at) = self.paused_at.take() {
+            self.total_paused_time += now.signed_duration_since(paused_at).num_seconds();
+        }
         self.is_indexing = false;
-        self.is_paused = false;
-        self.completed_at = Some(chrono::Utc::now());
+        self.completed_at = Some(now);
         self.progress_percentage = Some(100.0);
-        self.paused_at = None;
     }
     
-    /// Pauses indexing
+    /// Pauses indexing. Only time paused during an indexing run is recorded.
     pub fn pause_indexing(&mut self) {
-        if self.is_indexing && !self.is_paused {
+        if !self.is_paused {
             self.is_paused = true;
-            self.paused_at = Some(chrono::Utc::now());
+            self.paused_at = self.is_indexing.then(chrono::Utc::now);
         }
     }
     
     /// Resumes indexing
     pub fn resume_indexing(&mut self) {
-        if self.is_indexing && self.is_paused {
+        if self.is_paused {
             if let Some(paused_time) = self.paused_at {
                 // Calculate time spent in paused state and add to total
                 let now = chrono::Utc::now();
@@ -151,14 +201,14 @@ impl IndexingProgress {
     
     /// Gets a user-friendly status message
     pub fn status_message(&self) -> String {
-        if !self.is_indexing && self.completed_at.is_some() {
-            return format!("Indexing complete ({})", self.elapsed_time());
-        }
-        
         if self.is_paused {
             return format!("Indexing paused - {}", self.elapsed_time());
         }
         
+        if !self.is_indexing && self.completed_at.is_some() {
+            return format!("Indexing complete ({})", self.elapsed_time());
+        }
+        
         if let Some(msg) = &self.status_message {
             if let Some(percent) = self.progress_percentage {
                 return format!("{} ({:.0}%) - {}", msg, percent, self.elapsed_time());
@@ -174,6 +224,32 @@ impl IndexingProgress {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paused_time() {
+        let mut progress = IndexingProgress::new(PathBuf::from("/project"));
+        progress.start_indexing();
+        progress.pause_indexing();
+        assert!(progress.is_paused);
+        progress.paused_at = Some(chrono::Utc::now() - chrono::Duration::seconds(5));
+        progress.resume_indexing();
+        assert!(!progress.is_paused);
+        assert_eq!(progress.total_paused_time, 5);
+
+        // Indexing finishing during a pause records the pause, but keeps it
+        progress.pause_indexing();
+        progress.paused_at = Some(chrono::Utc::now() - chrono::Duration::seconds(3));
+        progress.complete_indexing();
+        assert!(progress.is_paused);
+        assert_eq!(progress.total_paused_time, 8);
+        progress.resume_indexing();
+        assert_eq!(progress.total_paused_time, 8);
+    }
+}
+
 /// Container module for LspError
 pub mod error {
     // ... existing code ...
@@ -1,42 +1,47 @@
 use std::path::Path;
-use std::process::Stdio;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::{Context, Result};
-use async_lsp::concurrency::ConcurrencyLayer;
-use async_lsp::panic::CatchUnwindLayer;
-use async_lsp::server::LifecycleLayer;
-use async_lsp::tracing::TracingLayer;
-use async_lsp::{LanguageServer, ServerSocket};
+use async_lsp::LanguageServer;
 use lsp_types::request::GotoTypeDefinitionParams;
 use lsp_types::{
-    ClientCapabilities, DidOpenTextDocumentParams, DocumentSymbolClientCapabilities,
-    GotoDefinitionResponse, Hover, HoverClientCapabilities, HoverParams, InitializeParams,
-    InitializedParams, Location, MarkupKind, Position, ReferenceContext, ReferenceParams,
-    TextDocumentClientCapabilities, TextDocumentIdentifier, TextDocumentItem,
-    TextDocumentPositionParams, WindowClientCapabilities, WorkDoneProgressParams, WorkspaceFolder,
+    CodeAction, CodeActionContext, CodeActionOrCommand, CodeActionParams,
+    CodeActionTriggerKind, CompletionContext, CompletionItem, CompletionParams, CompletionResponse,
+    CompletionTriggerKind, Diagnostic, DocumentDiagnosticParams, DocumentDiagnosticReport,
+    DidChangeWatchedFilesParams, DocumentDiagnosticReportResult, FileEvent, GotoDefinitionResponse, Hover, HoverParams, InlayHint, InlayHintParams, Location, Position,
+    PrepareRenameResponse, Range, ReferenceContext, ReferenceParams, RenameParams, SignatureHelp,
+    SignatureHelpParams, TextDocumentIdentifier, TextDocumentPositionParams,
+    WorkDoneProgressParams, WorkspaceEdit,
 };
-use serde_json::json;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedMutexGuard};
 use tokio::task::JoinHandle;
-use tower::ServiceBuilder;
-use tracing::{debug, info};
+use tracing::info;
+use url::Url;
 
 use super::change_notifier::ChangeNotifier;
-use super::client_state::ClientState;
-use crate::lsp::{LspNotification, IndexingProgress};
+use super::edits::{EditPlan, edited_uris};
+use super::extensions::{
+    ExpandMacro, ExpandMacroParams, ExpandedMacro, RecursiveMemoryLayout, RelatedTests, Runnable,
+    Runnables, RunnablesParams, Ssr, SsrParams, ViewCrateGraph, ViewCrateGraphParams, ViewHir,
+    ViewMir, ViewRecursiveMemoryLayout, ViewSyntaxTree, ViewSyntaxTreeParams,
+};
+use super::supervisor::Supervisor;
+use super::{
+    IndexingProgress, LspNotification, SharedProgress, insert_at, syntax_tree_text,
+    update_progress,
+};
 use crate::project::Project;
 use flume::Sender;
 
 #[derive(Debug)]
 pub struct RustAnalyzerLsp {
     project: Project,
-    server: Arc<Mutex<ServerSocket>>,
-    #[allow(dead_code)] // Keep the handle to ensure the mainloop runs
-    mainloop_handle: Mutex<Option<JoinHandle<()>>>,
+    notifier: Sender<LspNotification>,
+    progress: SharedProgress,
+    supervisor: Arc<Supervisor>,
+    supervisor_handle: JoinHandle<()>,
     indexed_rx: Mutex<flume::Receiver<()>>,
-    #[allow(dead_code)] // Keep the handle to ensure the change notifier runs
     change_notifier: ChangeNotifier,
     // Track whether initial indexing is complete to avoid infinite reindexing
     initial_indexing_complete: AtomicBool,
@@ -45,33 +50,25 @@ pub struct RustAnalyzerLsp {
 impl RustAnalyzerLsp {
     pub async fn new(project: &Project, notifier: Sender<LspNotification>) -> Result<Self> {
         let (indexed_tx, indexed_rx) = flume::unbounded();
-        
-        // Create a clone early for use in the client state
-        let notifier_for_client = notifier.clone();
-        
-        let (mainloop, server) = async_lsp::MainLoop::new_client(|_server| {
-            ServiceBuilder::new()
-                .layer(TracingLayer::default())
-                .layer(LifecycleLayer::default()) // Handle init/shutdown automatically
-                .layer(CatchUnwindLayer::default())
-                .layer(ConcurrencyLayer::default())
-                .service(ClientState::new_router(
-                    indexed_tx,
-                    notifier_for_client,
-                    project.root().clone(),
-                ))
-        });
 
         // Check if rust-analyzer is available AND works correctly
-        let is_installed = match tokio::process::Command::new("rust-analyzer")
+        let config = &project.rust_analyzer;
+        let is_installed = match tokio::process::Command::new(config.binary())
             .arg("--version")  // Try to run with --version to check if it really works
+            .envs(&config.env)
             .output()
             .await {
                 Ok(output) if output.status.success() => true,
                 _ => false,  // Command exists but fails or doesn't exist at all
             };
 
-        if !is_installed {
+        if !is_installed && (!config.auto_install || config.binary.is_some()) {
+            // Installing is opt-in, starting the server reports the missing binary
+            tracing::warn!(
+                "{:?} not found or not working properly. Set `rust_analyzer_auto_install = true` in the configuration to install it with rustup",
+                config.binary()
+            );
+        } else if !is_installed {
             // Attempt to install rust-analyzer using rustup if available
             tracing::warn!("rust-analyzer not found or not working properly. Attempting to install...");
             
@@ -177,128 +174,25 @@ impl RustAnalyzerLsp {
             }
         }
 
-        let process = match async_process::Command::new("rust-analyzer")
-            .current_dir(project.root())
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn() {
-                Ok(process) => process,
-                Err(e) => {
-                    // First attempt failed, try to locate rust-analyzer in standard paths
-                    tracing::warn!("Failed to run rust-analyzer directly: {}", e);
-                    
-                    if cfg!(windows) {
-                        // Try to locate rust-analyzer in standard Windows locations
-                        let mut rust_analyzer_path = None;
-                        
-                        // Check in .cargo/bin
-                        if let Some(home_dir) = dirs::home_dir() {
-                            let cargo_bin_path = home_dir.join(".cargo").join("bin").join("rust-analyzer.exe");
-                            if cargo_bin_path.exists() {
-                                tracing::info!("Found rust-analyzer.exe at: {:?}", cargo_bin_path);
-                                rust_analyzer_path = Some(cargo_bin_path);
-                            }
-                        }
-                        
-                        if let Some(path) = rust_analyzer_path {
-                            match async_process::Command::new(path)
-                                .current_dir(project.root())
-                                .stdin(Stdio::piped())
-                                .stdout(Stdio::piped())
-                                .stderr(Stdio::inherit())
-                                .spawn() {
-                                    Ok(process) => process,
-                                    Err(e) => {
-                                        return Err(anyhow::anyhow!(
-                                            "Failed to run rust-analyzer from found path: {}. Try manually installing with 'rustup component add rust-analyzer --toolchain stable-x86_64-pc-windows-msvc'", e
-                                        ));
-                                    }
-                                }
-                        } else {
-                            return Err(anyhow::anyhow!(
-                                "Could not locate rust-analyzer executable. Please run 'rustup component add rust-analyzer --toolchain stable-x86_64-pc-windows-msvc' to install it."
-                            ));
-                        }
-                    } else {
-                        // For non-Windows platforms, just report the original error
-                        return Err(anyhow::anyhow!(
-                            "Failed to run rust-analyzer: {}. Please make sure rust-analyzer is installed and available in your PATH.", e
-                        ));
-                    }
-                }
-            };
-
-        let stdout = process.stdout.context("Failed to get stdout")?;
-        let stdin = process.stdin.context("Failed to get stdin")?;
-
-        let mainloop_handle = tokio::spawn(async move {
-            match mainloop.run_buffered(stdout, stdin).await {
-                Ok(()) => debug!("LSP mainloop finished gracefully."),
-                Err(e) => tracing::error!("LSP mainloop finished with error: {}", e),
-            }
-        });
+        let progress: SharedProgress =
+            Arc::new(StdMutex::new(IndexingProgress::new(project.root().clone())));
+        update_progress(&progress, |progress| progress.start_indexing());
+        let (supervisor, supervisor_handle) =
+            Supervisor::start(project, indexed_tx, notifier.clone(), progress.clone()).await?;
 
-        let server = Arc::new(Mutex::new(server));
-
-        // Get the current runtime handle
-        let handle = tokio::runtime::Handle::current();
-        let change_notifier = ChangeNotifier::new(server.clone(), project, handle)?;
+        let change_notifier = ChangeNotifier::new(supervisor.clone(), project)?;
 
         let client = Self {
             project: project.clone(),
-            server,
-            mainloop_handle: Mutex::new(Some(mainloop_handle)),
+            notifier: notifier.clone(),
+            progress: progress.clone(),
+            supervisor,
+            supervisor_handle,
             indexed_rx: Mutex::new(indexed_rx),
             change_notifier,
             initial_indexing_complete: AtomicBool::new(false),
         };
 
-        // Initialize.
-        let init_ret = client
-            .server
-            .lock()
-            .await
-            .initialize(InitializeParams {
-                workspace_folders: Some(vec![WorkspaceFolder {
-                    uri: project.uri()?,
-                    name: "root".into(),
-                }]),
-                capabilities: ClientCapabilities {
-                    window: Some(WindowClientCapabilities {
-                        work_done_progress: Some(true), // Required for indexing progress
-                        ..WindowClientCapabilities::default()
-                    }),
-                    text_document: Some(TextDocumentClientCapabilities {
-                        document_symbol: Some(DocumentSymbolClientCapabilities {
-                            // Flat symbols are easier to process for us
-                            hierarchical_document_symbol_support: Some(false),
-                            ..DocumentSymbolClientCapabilities::default()
-                        }),
-                        hover: Some(HoverClientCapabilities {
-                            content_format: Some(vec![MarkupKind::Markdown]),
-                            ..HoverClientCapabilities::default()
-                        }),
-                        ..TextDocumentClientCapabilities::default()
-                    }),
-                    experimental: Some(json!({
-                        "hoverActions": true
-                    })),
-                    ..ClientCapabilities::default()
-                },
-                ..InitializeParams::default()
-            })
-            .await?;
-        tracing::trace!("Initialized: {init_ret:?}");
-        info!("LSP Initialized");
-
-        client
-            .server
-            .lock()
-            .await
-            .initialized(InitializedParams {})
-            .context("Sending Initialized notification failed")?;
-
         info!("Waiting for rust-analyzer indexing...");
         let rx = client.indexed_rx.lock().await.clone();
         
@@ -307,16 +201,12 @@ impl RustAnalyzerLsp {
         let notifier_clone2 = notifier.clone();
         client.initial_indexing_complete.store(false, Ordering::SeqCst);
         let _task = tokio::spawn(async move {
-            // Create progress instance for this thread
-            let mut progress = IndexingProgress::new(project_path.clone());
-            progress.start_indexing();
-            
             // We only care about the first completion signal
             if let Ok(()) = rx.recv_async().await {
                 info!("rust-analyzer initial indexing finished");
                 
                 // Mark indexing as complete
-                progress.complete_indexing();
+                let progress = update_progress(&progress, |progress| progress.complete_indexing());
                 
                 // Send explicit "indexing finished" notification to update UI
                 if let Err(e) = notifier_clone2.try_send(LspNotification::IndexingProgress(progress)) {
@@ -354,103 +244,48 @@ impl RustAnalyzerLsp {
     }
 
     pub async fn shutdown(&self) -> Result<()> {
-        // Try to acquire the lock with a timeout to avoid deadlock
-        let server_lock_result = tokio::time::timeout(
-            std::time::Duration::from_secs(2),
-            self.server.lock()
-        ).await;
-
-        // Handle timeout or lock acquisition errors
-        let mut server_guard = match server_lock_result {
-            Ok(guard) => guard,
-            Err(_) => {
-                tracing::warn!("Timeout acquiring server lock during shutdown");
-                // Return success since this isn't fatal
-                return Ok(());
-            }
-        };
-
-        // Try shutdown but don't fail if it errors
-        if let Err(e) = server_guard.shutdown(()).await {
-            tracing::warn!("Error during LSP shutdown request: {:?}", e);
-            // Continue with exit anyway
-        }
-
-        // Try exit but don't fail if it errors
-        if let Err(e) = server_guard.exit(()) {
-            tracing::warn!("Error during LSP exit notification: {:?}", e);
-        }
+        let result = self.supervisor.shutdown().await;
+        self.supervisor_handle.abort();
+        result
+    }
 
-        // Release server lock before waiting for mainloop
-        drop(server_guard);
-
-        // Try to get the mainloop handle with a timeout
-        let mainloop_lock_result = tokio::time::timeout(
-            std::time::Duration::from_secs(2),
-            self.mainloop_handle.lock()
-        ).await;
-
-        // Handle timeout or lock acquisition errors for mainloop handle
-        match mainloop_lock_result {
-            Ok(mut guard) => {
-                // Only join if there's a handle to take
-                if let Some(handle) = guard.take() {
-                    // Don't wait indefinitely - use a timeout
-                    match tokio::time::timeout(std::time::Duration::from_secs(5), handle).await {
-                        Ok(join_result) => {
-                            if let Err(e) = join_result {
-                                tracing::warn!("Error joining LSP mainloop task: {:?}", e);
-                            }
-                        },
-                        Err(_) => tracing::warn!("Timeout waiting for LSP mainloop to finish"),
-                    }
-                }
-            },
-            Err(_) => tracing::warn!("Timeout acquiring mainloop handle lock during shutdown"),
+    /// Pauses or resumes indexing. While paused, file changes aren't sent to
+    /// rust-analyzer and requests wait for indexing to be resumed.
+    pub async fn set_paused(&self, paused: bool) -> Result<()> {
+        let progress = update_progress(&self.progress, |progress| {
+            if paused {
+                progress.pause_indexing();
+            } else {
+                progress.resume_indexing();
+            }
+        });
+        if let Err(e) = self.notifier.try_send(LspNotification::IndexingProgress(progress)) {
+            tracing::debug!("Failed to send paused progress: {}", e);
         }
-
-        Ok(())
+        self.change_notifier.set_paused(paused);
+        self.supervisor.set_paused(paused).await
     }
 
-    #[allow(dead_code)]
-    pub async fn open_file(&self, relative_path: impl AsRef<Path>, text: String) -> Result<()> {
-        let path_ref = relative_path.as_ref();
-        let uri = self.project.file_uri(path_ref)?;
-        self.server
-            .lock()
-            .await
-            .did_open(DidOpenTextDocumentParams {
-                text_document: TextDocumentItem {
-                    uri: uri.clone(),
-                    language_id: "rust".into(), // Assuming Rust, could be made generic
-                    version: 0,                 // Start with version 0
-                    text,
-                },
-            })
-            .context("Sending DidOpen notification failed")?;
+    /// Sends the contents of an editor buffer, which may differ from the file
+    /// on disk. Until the document is closed, rust-analyzer answers from these
+    /// contents. Returns the version of the document.
+    pub async fn update_document(&self, relative_path: impl AsRef<Path>, text: String) -> Result<i32> {
+        let uri = self.project.file_uri(relative_path)?;
+        let _lock = self.supervisor.lock_document(&uri).await;
+        self.supervisor.update_document(uri, text).await
+    }
 
-        // Check if indexing is already complete
-        if self.initial_indexing_complete.load(Ordering::SeqCst) {
-            // Skip waiting for indexing signals if we've already completed indexing
-            tracing::debug!("Skipping indexing wait for file (already indexed)");
-            return Ok(());
-        }
+    /// Closes a document opened with `update_document`, so rust-analyzer reads
+    /// the file from disk again. Returns whether it was open.
+    pub async fn close_document(&self, relative_path: impl AsRef<Path>) -> Result<bool> {
+        let uri = self.project.file_uri(relative_path)?;
+        let _lock = self.supervisor.lock_document(&uri).await;
+        self.supervisor.close_document(uri).await
+    }
 
-        tracing::debug!("Waiting for indexing to complete for file: {:?}", path_ref);
-        
-        // Wait for indexing to complete
-        self.indexed_rx
-            .lock()
-            .await
-            .recv_async()
-            .await
-            .context("Failed waiting for index")?;
-        
-        // Mark indexing as complete
-        self.initial_indexing_complete.store(true, Ordering::SeqCst);
-        tracing::debug!("Indexing completed while opening file: {:?}", path_ref);
-        
-        Ok(())
+    /// The URIs and versions of the documents sent with `update_document`
+    pub async fn open_documents(&self) -> Vec<(Url, i32)> {
+        self.supervisor.open_documents().await
     }
 
     pub async fn hover(
@@ -459,18 +294,16 @@ impl RustAnalyzerLsp {
         position: Position,
     ) -> Result<Option<Hover>> {
         let uri = self.project.file_uri(relative_path)?;
-        self.server
-            .lock()
-            .await
-            .hover(HoverParams {
-                text_document_position_params: TextDocumentPositionParams {
-                    text_document: TextDocumentIdentifier { uri },
-                    position,
-                },
-                work_done_progress_params: WorkDoneProgressParams::default(),
-            })
+        let params = HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        self.supervisor
+            .request("Hover", async |server| server.hover(params).await)
             .await
-            .context("Hover request failed")
     }
 
     pub async fn type_definition(
@@ -479,19 +312,19 @@ impl RustAnalyzerLsp {
         position: Position,
     ) -> Result<Option<GotoDefinitionResponse>> {
         let uri = self.project.file_uri(relative_path)?;
-        self.server
-            .lock()
-            .await
-            .type_definition(GotoTypeDefinitionParams {
-                text_document_position_params: TextDocumentPositionParams {
-                    text_document: TextDocumentIdentifier { uri },
-                    position,
-                },
-                work_done_progress_params: WorkDoneProgressParams::default(),
-                partial_result_params: Default::default(),
+        let params = GotoTypeDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
+        self.supervisor
+            .request("Type definition", async |server| {
+                server.type_definition(params).await
             })
             .await
-            .context("Type definition request failed")
     }
 
     pub async fn find_references(
@@ -500,22 +333,20 @@ impl RustAnalyzerLsp {
         position: Position,
     ) -> Result<Option<Vec<Location>>> {
         let uri = self.project.file_uri(relative_path)?;
-        self.server
-            .lock()
-            .await
-            .references(ReferenceParams {
-                text_document_position: TextDocumentPositionParams {
-                    text_document: TextDocumentIdentifier { uri },
-                    position,
-                },
-                work_done_progress_params: WorkDoneProgressParams::default(),
-                partial_result_params: Default::default(),
-                context: ReferenceContext {
-                    include_declaration: true,
-                },
-            })
+        let params = ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        };
+        self.supervisor
+            .request("References", async |server| server.references(params).await)
             .await
-            .context("References request failed")
     }
 
     pub async fn document_symbols(
@@ -523,17 +354,17 @@ impl RustAnalyzerLsp {
         relative_path: impl AsRef<Path>,
     ) -> Result<Option<Vec<lsp_types::SymbolInformation>>> {
         let uri = self.project.file_uri(relative_path)?;
+        let params = lsp_types::DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
         let o = self
-            .server
-            .lock()
-            .await
-            .document_symbol(lsp_types::DocumentSymbolParams {
-                text_document: TextDocumentIdentifier { uri },
-                work_done_progress_params: WorkDoneProgressParams::default(),
-                partial_result_params: Default::default(),
+            .supervisor
+            .request("Document symbols", async |server| {
+                server.document_symbol(params).await
             })
-            .await
-            .context("Document symbols request failed")?
+            .await?
             .and_then(|symbols| match symbols {
                 lsp_types::DocumentSymbolResponse::Flat(f) => Some(f),
                 lsp_types::DocumentSymbolResponse::Nested(_) => {
@@ -543,4 +374,546 @@ impl RustAnalyzerLsp {
             });
         Ok(o)
     }
+
+    /// Searches all symbols of the workspace and its dependencies
+    pub async fn workspace_symbols(
+        &self,
+        query: &str,
+    ) -> Result<Vec<lsp_types::SymbolInformation>> {
+        // rust-analyzer only searches types of the workspace by default: `#`
+        // includes all kinds of symbols and `*` the dependencies
+        let params = lsp_types::WorkspaceSymbolParams {
+            query: format!("{query}#*"),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
+        let response = self
+            .supervisor
+            .request("Workspace symbols", async |server| server.symbol(params).await)
+            .await?;
+        Ok(match response {
+            Some(lsp_types::WorkspaceSymbolResponse::Flat(symbols)) => symbols,
+            Some(lsp_types::WorkspaceSymbolResponse::Nested(_)) => {
+                tracing::error!("Only support flat symbols for now");
+                Vec::new()
+            }
+            None => Vec::new(),
+        })
+    }
+
+    /// The contents rust-analyzer sees for a file: the synced buffer if the
+    /// document is open, otherwise the file on disk
+    pub async fn document_text(&self, relative_path: impl AsRef<Path>) -> Result<String> {
+        let uri = self.project.file_uri(&relative_path)?;
+        if let Some(document) = self.supervisor.document(&uri).await {
+            return Ok(document.text);
+        }
+        let path = self.project.root().join(relative_path);
+        tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// Type, parameter name and chaining hints within `range`
+    pub async fn inlay_hints(
+        &self,
+        relative_path: impl AsRef<Path>,
+        range: Range,
+    ) -> Result<Vec<InlayHint>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = InlayHintParams {
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            text_document: TextDocumentIdentifier { uri },
+            range,
+        };
+        Ok(self
+            .supervisor
+            .request("Inlay hints", async |server| server.inlay_hint(params).await)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// The signatures of the call around `position`
+    pub async fn signature_help(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+    ) -> Result<Option<SignatureHelp>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        self.supervisor
+            .request("Signature help", async |server| {
+                server.signature_help(params).await
+            })
+            .await
+    }
+
+    /// The first `limit` completion items at `position` whose label contains
+    /// `filter`, ranked like an editor shows them and resolved to include
+    /// their documentation and imports. With `insert`, the text is inserted
+    /// at `position` first, in a synced document that is restored afterwards.
+    /// Until then, the document is locked against other changes.
+    pub async fn completion(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+        insert: Option<&str>,
+        filter: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<CompletionItem>> {
+        let relative_path = relative_path.as_ref();
+        let Some(insert) = insert.filter(|insert| !insert.is_empty()) else {
+            return self
+                .completion_items(relative_path, position, filter, limit)
+                .await;
+        };
+
+        let uri = self.project.file_uri(relative_path)?;
+        let lock = self.supervisor.lock_document(&uri).await;
+        let previous = self.supervisor.document(&uri).await;
+        let content = match &previous {
+            Some(document) => document.text.clone(),
+            None => self.document_text(relative_path).await?,
+        };
+        let (content, position) = insert_at(&content, position, insert);
+        let restore = RestoreDocument {
+            supervisor: self.supervisor.clone(),
+            uri: uri.clone(),
+            previous: previous.map(|document| document.text),
+            lock: Some(lock),
+        };
+        self.supervisor.update_document(uri, content).await?;
+
+        let items = self
+            .completion_items(relative_path, position, filter, limit)
+            .await;
+        restore.restore().await;
+        items
+    }
+
+    async fn completion_items(
+        &self,
+        relative_path: &Path,
+        position: Position,
+        filter: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<CompletionItem>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+            context: Some(CompletionContext {
+                trigger_kind: CompletionTriggerKind::INVOKED,
+                trigger_character: None,
+            }),
+        };
+        let items = match self
+            .supervisor
+            .request("Completion", async |server| server.completion(params).await)
+            .await?
+        {
+            Some(CompletionResponse::Array(items)) => items,
+            Some(CompletionResponse::List(list)) => list.items,
+            None => Vec::new(),
+        };
+        let filter = filter.map(|filter| filter.to_lowercase());
+        let mut items: Vec<_> = items
+            .into_iter()
+            .filter(|item| {
+                filter
+                    .as_ref()
+                    .is_none_or(|filter| item.label.to_lowercase().contains(filter))
+            })
+            .collect();
+        items.sort_by(|a, b| {
+            let key = |item: &CompletionItem| {
+                (
+                    item.preselect != Some(true),
+                    item.sort_text.clone().unwrap_or_else(|| item.label.clone()),
+                )
+            };
+            key(a).cmp(&key(b))
+        });
+
+        items.truncate(limit);
+
+        // Requests run concurrently, so resolving doesn't take one round trip per item
+        let resolved = futures::future::join_all(items.into_iter().map(async |item| {
+            let unresolved = item.clone();
+            match self
+                .supervisor
+                .request("Resolve completion", async |server| {
+                    server.completion_item_resolve(unresolved).await
+                })
+                .await
+            {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    tracing::debug!("Failed to resolve completion {}: {:?}", item.label, e);
+                    item
+                }
+            }
+        }))
+        .await;
+        Ok(resolved)
+    }
+
+    /// The range of the symbol at `position` if it can be renamed, or an
+    /// error explaining why not
+    pub async fn prepare_rename(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        };
+        self.supervisor
+            .request("Prepare rename", async |server| {
+                server.prepare_rename(params).await
+            })
+            .await
+    }
+
+    /// The edits renaming the symbol at `position` everywhere it is used,
+    /// including the file of a renamed module
+    pub async fn rename(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        self.supervisor
+            .request("Rename", async |server| server.rename(params).await)
+            .await
+    }
+
+    /// The diagnostics rust-analyzer computes itself for a file. Diagnostics
+    /// of `cargo check` aren't included.
+    pub async fn diagnostics(&self, relative_path: impl AsRef<Path>) -> Result<Vec<Diagnostic>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri },
+            identifier: None,
+            previous_result_id: None,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
+        let report = self
+            .supervisor
+            .request("Diagnostics", async |server| {
+                server.document_diagnostic(params).await
+            })
+            .await?;
+        Ok(match report {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                report.full_document_diagnostic_report.items
+            }
+            // We never send a previous result id, so there is nothing it could be unchanged from
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(_))
+            | DocumentDiagnosticReportResult::Partial(_) => Vec::new(),
+        })
+    }
+
+    /// The code actions (quick fixes and assists) available for `range`.
+    /// Passing the diagnostics in the range includes their fixes.
+    pub async fn code_actions(
+        &self,
+        relative_path: impl AsRef<Path>,
+        range: Range,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Result<Vec<CodeActionOrCommand>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context: CodeActionContext {
+                diagnostics,
+                only: None,
+                trigger_kind: Some(CodeActionTriggerKind::INVOKED),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .supervisor
+            .request("Code actions", async |server| server.code_action(params).await)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Fills in the edit of a code action, which rust-analyzer only computes
+    /// when it is about to be applied
+    pub async fn resolve_code_action(&self, action: CodeAction) -> Result<CodeAction> {
+        if action.edit.is_some() || action.data.is_none() {
+            return Ok(action);
+        }
+        self.supervisor
+            .request("Resolve code action", async |server| {
+                server.code_action_resolve(action).await
+            })
+            .await
+    }
+
+    /// Computes the effect of a `WorkspaceEdit` on the synced buffers and the
+    /// files on disk. Unless it is a dry run, the changes are written and
+    /// rust-analyzer is told about them right away instead of waiting for the
+    /// file watcher.
+    pub async fn apply_edit(&self, edit: &WorkspaceEdit, dry_run: bool) -> Result<EditPlan> {
+        // Locked in order, so two edits of the same files can't deadlock
+        let mut locks = Vec::new();
+        for uri in edited_uris(edit) {
+            locks.push(self.supervisor.lock_document(&uri).await);
+        }
+        let documents = self.supervisor.documents().await;
+        let plan = EditPlan::new(self.project.root(), edit, &documents)?;
+        if dry_run || plan.is_empty() {
+            return Ok(plan);
+        }
+        plan.apply()?;
+
+        let mut events = Vec::new();
+        for change in plan.file_changes() {
+            let uri = Url::from_file_path(change.path)
+                .map_err(|_| anyhow::anyhow!("Failed to create file URI"))?;
+            // Open buffers follow the files, or rust-analyzer would keep the old contents
+            if documents.iter().any(|document| document.uri == uri) {
+                match change.content {
+                    Some(content) => {
+                        self.supervisor
+                            .update_document(uri.clone(), content.to_string())
+                            .await?;
+                    }
+                    None => {
+                        self.supervisor.close_document(uri.clone()).await?;
+                    }
+                }
+            }
+            events.push(FileEvent::new(uri, change.typ));
+        }
+        self.supervisor
+            .server()
+            .did_change_watched_files(DidChangeWatchedFilesParams { changes: events })
+            .context("Sending DidChangeWatchedFiles notification failed")?;
+        Ok(plan)
+    }
+
+    /// Runs a structural search and replace rule over the workspace, e.g.
+    /// `foo($a, $b) ==>> bar($b, $a)`. Paths in the rule are resolved at
+    /// `position`. With `parse_only`, the rule is only checked.
+    pub async fn ssr(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+        query: &str,
+        parse_only: bool,
+    ) -> Result<WorkspaceEdit> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = SsrParams {
+            query: query.to_string(),
+            parse_only,
+            position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            selections: Vec::new(),
+        };
+        self.supervisor
+            .request("Structural search and replace", async |server| {
+                server.request::<Ssr>(params).await
+            })
+            .await
+    }
+
+    /// The recursive expansion of the macro call or attribute at `position`
+    pub async fn expand_macro(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+    ) -> Result<Option<ExpandedMacro>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = ExpandMacroParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        };
+        self.supervisor
+            .request("Expand macro", async |server| {
+                server.request::<ExpandMacro>(params).await
+            })
+            .await
+    }
+
+    /// The tests, benches, binaries and doctests in a file, or only those
+    /// around `position`, each with the exact cargo invocation to run it
+    pub async fn runnables(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Option<Position>,
+    ) -> Result<Vec<Runnable>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = RunnablesParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        };
+        self.supervisor
+            .request("Runnables", async |server| {
+                server.request::<Runnables>(params).await
+            })
+            .await
+    }
+
+    /// The tests that exercise the function at `position`, as runnables
+    pub async fn related_tests(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+    ) -> Result<Vec<Runnable>> {
+        let uri = self.project.file_uri(relative_path)?;
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        };
+        let tests = self
+            .supervisor
+            .request("Related tests", async |server| {
+                server.request::<RelatedTests>(params).await
+            })
+            .await?;
+        Ok(tests.into_iter().map(|test| test.runnable).collect())
+    }
+
+    /// The HIR of the function at `position`
+    pub async fn view_hir(&self, relative_path: impl AsRef<Path>, position: Position) -> Result<String> {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: self.project.file_uri(relative_path)? },
+            position,
+        };
+        self.supervisor
+            .request("View HIR", async |server| server.request::<ViewHir>(params).await)
+            .await
+    }
+
+    /// The MIR of the function at `position`
+    pub async fn view_mir(&self, relative_path: impl AsRef<Path>, position: Position) -> Result<String> {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: self.project.file_uri(relative_path)? },
+            position,
+        };
+        self.supervisor
+            .request("View MIR", async |server| server.request::<ViewMir>(params).await)
+            .await
+    }
+
+    /// The syntax tree of a file as indented `KIND@start..end` lines
+    pub async fn view_syntax_tree(&self, relative_path: impl AsRef<Path>) -> Result<String> {
+        let params = ViewSyntaxTreeParams {
+            text_document: TextDocumentIdentifier { uri: self.project.file_uri(relative_path)? },
+        };
+        let tree = self
+            .supervisor
+            .request("View syntax tree", async |server| {
+                server.request::<ViewSyntaxTree>(params).await
+            })
+            .await?;
+        Ok(syntax_tree_text(&tree))
+    }
+
+    /// The size, alignment and field offsets of the type at `position`
+    pub async fn memory_layout(
+        &self,
+        relative_path: impl AsRef<Path>,
+        position: Position,
+    ) -> Result<Option<RecursiveMemoryLayout>> {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: self.project.file_uri(relative_path)? },
+            position,
+        };
+        self.supervisor
+            .request("Memory layout", async |server| {
+                server.request::<ViewRecursiveMemoryLayout>(params).await
+            })
+            .await
+    }
+
+    /// The crate graph in DOT format: only the workspace crates, or with
+    /// `dependencies` all crates including the standard library
+    pub async fn crate_graph(&self, dependencies: bool) -> Result<String> {
+        let params = ViewCrateGraphParams { full: dependencies };
+        self.supervisor
+            .request("Crate graph", async |server| {
+                server.request::<ViewCrateGraph>(params).await
+            })
+            .await
+    }
+}
+
+/// Undoes a temporary change of a synced document and releases its lock.
+/// If it's dropped without `restore`, e.g. because the request was
+/// cancelled, the document is restored in a task.
+struct RestoreDocument {
+    supervisor: Arc<Supervisor>,
+    uri: Url,
+    /// The text of the synced document before, `None` if it wasn't open
+    previous: Option<String>,
+    lock: Option<OwnedMutexGuard<()>>,
+}
+
+impl RestoreDocument {
+    async fn restore(mut self) {
+        // Restoring twice does no harm, if this is cancelled halfway
+        restore_document(&self.supervisor, self.uri.clone(), self.previous.clone()).await;
+        self.lock.take();
+    }
+}
+
+impl Drop for RestoreDocument {
+    fn drop(&mut self) {
+        let Some(lock) = self.lock.take() else {
+            return;
+        };
+        let supervisor = self.supervisor.clone();
+        let uri = self.uri.clone();
+        let previous = self.previous.take();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    restore_document(&supervisor, uri, previous).await;
+                    drop(lock);
+                });
+            }
+            Err(_) => tracing::error!("Failed to restore {} outside of a runtime", uri),
+        }
+    }
+}
+
+async fn restore_document(supervisor: &Supervisor, uri: Url, previous: Option<String>) {
+    let restored = match previous {
+        Some(text) => supervisor.update_document(uri.clone(), text).await.map(|_| ()),
+        None => supervisor.close_document(uri.clone()).await.map(|_| ()),
+    };
+    if let Err(e) = restored {
+        tracing::error!("Failed to restore {} after a temporary change: {:?}", uri, e);
+    }
 }
@@ -0,0 +1,881 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_lsp::concurrency::ConcurrencyLayer;
+use async_lsp::panic::CatchUnwindLayer;
+use async_lsp::server::LifecycleLayer;
+use async_lsp::tracing::TracingLayer;
+use async_lsp::{LanguageServer, ServerSocket};
+use lsp_types::{
+    ClientCapabilities, CodeActionCapabilityResolveSupport, CodeActionClientCapabilities,
+    CodeActionKindLiteralSupport, CodeActionLiteralSupport, CompletionClientCapabilities, CompletionItemCapability,
+    CompletionItemCapabilityResolveSupport, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DiagnosticClientCapabilities, DidOpenTextDocumentParams, DocumentSymbolClientCapabilities, ParameterInformationSettings,
+    SignatureHelpClientCapabilities, SignatureInformationSettings,
+    HoverClientCapabilities, InitializeParams, InitializedParams, MarkupKind,
+    RenameClientCapabilities, ResourceOperationKind, WorkspaceEditClientCapabilities,
+    TextDocumentClientCapabilities, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+    TextDocumentItem, WindowClientCapabilities,
+    WorkspaceClientCapabilities, WorkspaceFolder,
+};
+use serde_json::json;
+use tokio::sync::{Mutex, OwnedMutexGuard, Semaphore, watch};
+use tokio::task::JoinHandle;
+use tower::ServiceBuilder;
+use tracing::{debug, info};
+use url::Url;
+
+use super::client_state::ClientState;
+use super::documents::{DocumentUpdate, OpenDocuments};
+use super::{LspNotification, ServerHealth, SharedProgress};
+use crate::project::Project;
+
+/// After this many timeouts in a row, rust-analyzer is considered hung
+const MAX_CONSECUTIVE_TIMEOUTS: usize = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A server that ran this long before failing restarts without backoff
+const HEALTHY_AFTER: Duration = Duration::from_secs(120);
+/// How long a request waits for indexing to be resumed before giving up
+const PAUSED_REQUEST_WAIT: Duration = Duration::from_secs(30);
+
+/// Why a rust-analyzer instance needs to be replaced
+#[derive(Debug)]
+struct Failure {
+    /// The instance that failed. Failures of older instances are ignored.
+    generation: u64,
+    reason: String,
+}
+
+/// The rust-analyzer process and the task driving its main loop
+#[derive(Debug)]
+struct ServerProcess {
+    child: async_process::Child,
+    mainloop: JoinHandle<()>,
+}
+
+/// Keeps rust-analyzer alive: detects when the process exits or stops
+/// answering, restarts it with backoff, re-runs `initialize`/`initialized`
+/// and reopens the tracked documents.
+///
+/// Requests run concurrently on clones of the server socket, limited by
+/// the project's `max_concurrent_requests`.
+#[derive(Debug)]
+pub(super) struct Supervisor {
+    project: Project,
+    /// Swapped for the socket of the new instance on every restart
+    server: RwLock<ServerSocket>,
+    /// Limits the requests in flight
+    request_slots: Semaphore,
+    request_timeout: Duration,
+    process: Mutex<Option<ServerProcess>>,
+    /// Documents opened with `didOpen`, reopened after a restart
+    documents: Mutex<OpenDocuments>,
+    /// Held while a document is changed, see `lock_document`
+    document_locks: std::sync::Mutex<HashMap<Url, Arc<Mutex<()>>>>,
+    indexed_tx: flume::Sender<()>,
+    notifier: flume::Sender<LspNotification>,
+    progress: SharedProgress,
+    failure_tx: flume::Sender<Failure>,
+    /// While true, requests are held back until indexing is resumed
+    paused: watch::Sender<bool>,
+    generation: AtomicU64,
+    is_running: AtomicBool,
+    stopped: AtomicBool,
+    restart_count: AtomicUsize,
+    consecutive_timeouts: AtomicUsize,
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+impl Supervisor {
+    /// Spawns and initializes rust-analyzer and starts supervising it
+    pub async fn start(
+        project: &Project,
+        indexed_tx: flume::Sender<()>,
+        notifier: flume::Sender<LspNotification>,
+        progress: SharedProgress,
+    ) -> Result<(Arc<Self>, JoinHandle<()>)> {
+        let (failure_tx, failure_rx) = flume::unbounded();
+        let (mut server, process) =
+            spawn_server(project, &indexed_tx, &notifier, &progress, &failure_tx, 0)?;
+        initialize(&mut server, project).await?;
+
+        let supervisor = Arc::new(Self::new(
+            project,
+            server,
+            Some(process),
+            indexed_tx,
+            notifier,
+            progress,
+            failure_tx,
+        ));
+        let handle = tokio::spawn(supervisor.clone().supervise(failure_rx));
+        Ok((supervisor, handle))
+    }
+
+    fn new(
+        project: &Project,
+        server: ServerSocket,
+        process: Option<ServerProcess>,
+        indexed_tx: flume::Sender<()>,
+        notifier: flume::Sender<LspNotification>,
+        progress: SharedProgress,
+        failure_tx: flume::Sender<Failure>,
+    ) -> Self {
+        Self {
+            project: project.clone(),
+            server: RwLock::new(server),
+            request_slots: Semaphore::new(project.rust_analyzer.max_concurrent_requests()),
+            request_timeout: project.rust_analyzer.request_timeout(),
+            process: Mutex::new(process),
+            documents: Mutex::new(OpenDocuments::default()),
+            document_locks: Default::default(),
+            indexed_tx,
+            notifier,
+            progress,
+            failure_tx,
+            paused: watch::Sender::new(false),
+            generation: AtomicU64::new(0),
+            is_running: AtomicBool::new(true),
+            stopped: AtomicBool::new(false),
+            restart_count: AtomicUsize::new(0),
+            consecutive_timeouts: AtomicUsize::new(0),
+            last_error: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// A handle to the current rust-analyzer instance. Clones share the
+    /// connection, so requests on them run concurrently.
+    pub fn server(&self) -> ServerSocket {
+        self.server
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Sends a request to rust-analyzer. Requests that time out or find the
+    /// server gone are reported, so a dead or hung server gets restarted.
+    pub async fn request<T>(
+        &self,
+        method: &str,
+        request: impl AsyncFnOnce(&mut ServerSocket) -> async_lsp::Result<T>,
+    ) -> Result<T> {
+        if *self.paused.borrow() {
+            let mut paused = self.paused.subscribe();
+            let resumed = tokio::time::timeout(PAUSED_REQUEST_WAIT, paused.wait_for(|p| !p)).await;
+            if !matches!(resumed, Ok(Ok(_))) {
+                return Err(anyhow::anyhow!(
+                    "Indexing is paused for {:?}, so the {method} request was not sent. Resume indexing and try again",
+                    self.project.root()
+                ));
+            }
+        }
+        if !self.is_running.load(Ordering::SeqCst) {
+            return Err(match self.last_error() {
+                Some(error) => anyhow::anyhow!(
+                    "rust-analyzer is restarting after an error: {error}. Please try again shortly"
+                ),
+                None => anyhow::anyhow!("rust-analyzer is restarting. Please try again shortly"),
+            });
+        }
+
+        // Waiting for a free slot doesn't count towards the timeout
+        let _slot = self
+            .request_slots
+            .acquire()
+            .await
+            .context("rust-analyzer client is shutting down")?;
+        let generation = self.generation.load(Ordering::SeqCst);
+        let mut server = self.server();
+        let response = tokio::time::timeout(self.request_timeout, request(&mut server)).await;
+        match response {
+            Ok(Ok(result)) => {
+                self.consecutive_timeouts.store(0, Ordering::SeqCst);
+                Ok(result)
+            }
+            Ok(Err(e)) => {
+                if matches!(e, async_lsp::Error::ServiceStopped | async_lsp::Error::Eof) {
+                    self.report_failure(generation, format!("{method} request failed: {e}"));
+                }
+                Err(anyhow::Error::new(e).context(format!("{method} request failed")))
+            }
+            Err(_) => {
+                let timeouts = self.consecutive_timeouts.fetch_add(1, Ordering::SeqCst) + 1;
+                if timeouts >= MAX_CONSECUTIVE_TIMEOUTS {
+                    self.report_failure(
+                        generation,
+                        format!("{timeouts} requests in a row timed out"),
+                    );
+                }
+                Err(anyhow::anyhow!(
+                    "{method} request timed out after {}s",
+                    self.request_timeout.as_secs()
+                ))
+            }
+        }
+    }
+
+    /// Keeps others from changing a document until the guard is dropped, so
+    /// a temporary change and its undo aren't interleaved with other changes.
+    /// `update_document` and `close_document` don't take it themselves.
+    pub async fn lock_document(&self, uri: &Url) -> OwnedMutexGuard<()> {
+        let lock = self
+            .document_locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(uri.clone())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    /// Sends new contents of a document with `didOpen` or `didChange` and
+    /// remembers them, so the document can be reopened after a restart.
+    /// Returns the version of the document.
+    pub async fn update_document(&self, uri: Url, text: String) -> Result<i32> {
+        // Hold the lock while sending, so versions arrive in order
+        let mut documents = self.documents.lock().await;
+        match documents.update(uri.clone(), text) {
+            DocumentUpdate::Open(document) => self
+                .server()
+                .did_open(DidOpenTextDocumentParams {
+                    text_document: document,
+                })
+                .context("Sending DidOpen notification failed")?,
+            DocumentUpdate::Change(text_document, text) => self
+                .server()
+                .did_change(DidChangeTextDocumentParams {
+                    text_document,
+                    content_changes: vec![TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text,
+                    }],
+                })
+                .context("Sending DidChange notification failed")?,
+            DocumentUpdate::Unchanged => (),
+        }
+        Ok(documents.get(&uri).map(|document| document.version).unwrap_or_default())
+    }
+
+    /// Closes a document, so rust-analyzer reads it from disk again.
+    /// Returns whether it was open.
+    pub async fn close_document(&self, uri: Url) -> Result<bool> {
+        let mut documents = self.documents.lock().await;
+        if !documents.close(&uri) {
+            return Ok(false);
+        }
+        self.server()
+            .did_close(DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri },
+            })
+            .context("Sending DidClose notification failed")?;
+        Ok(true)
+    }
+
+    /// The open document for `uri`, if any
+    pub async fn document(&self, uri: &Url) -> Option<TextDocumentItem> {
+        self.documents.lock().await.get(uri).cloned()
+    }
+
+    /// All open documents with their contents
+    pub async fn documents(&self) -> Vec<TextDocumentItem> {
+        self.documents.lock().await.iter().cloned().collect()
+    }
+
+    /// The URIs and versions of all open documents
+    pub async fn open_documents(&self) -> Vec<(Url, i32)> {
+        let mut documents: Vec<_> = self
+            .documents
+            .lock()
+            .await
+            .iter()
+            .map(|document| (document.uri.clone(), document.version))
+            .collect();
+        documents.sort();
+        documents
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
+        // Exiting on purpose, so the mainloop ending is no failure
+        self.stopped.store(true, Ordering::SeqCst);
+
+        let mut server = self.server();
+
+        // Try shutdown but don't fail if it errors, nor wait forever
+        match tokio::time::timeout(Duration::from_secs(2), server.shutdown(())).await {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => tracing::warn!("Error during LSP shutdown request: {:?}", e),
+            Err(_) => {
+                tracing::warn!("Timeout waiting for LSP shutdown response");
+                self.stop_process().await;
+                return Ok(());
+            }
+        }
+
+        // Try exit but don't fail if it errors
+        if let Err(e) = server.exit(()) {
+            tracing::warn!("Error during LSP exit notification: {:?}", e);
+        }
+
+        let process = self.process.lock().await.take();
+        if let Some(mut process) = process {
+            // Don't wait indefinitely - use a timeout
+            match tokio::time::timeout(Duration::from_secs(5), &mut process.mainloop).await {
+                Ok(join_result) => {
+                    if let Err(e) = join_result {
+                        tracing::warn!("Error joining LSP mainloop task: {:?}", e);
+                    }
+                }
+                Err(_) => {
+                    tracing::warn!("Timeout waiting for LSP mainloop to finish");
+                    process.mainloop.abort();
+                    if let Err(e) = process.child.kill() {
+                        tracing::warn!("Failed to kill rust-analyzer: {:?}", e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pauses or resumes forwarding requests. With `stop_on_pause`
+    /// configured, rust-analyzer is stopped while paused and started
+    /// again on resume.
+    pub async fn set_paused(&self, paused: bool) -> Result<()> {
+        if self.paused.send_replace(paused) == paused {
+            return Ok(());
+        }
+        if !self.project.rust_analyzer.stop_on_pause {
+            return Ok(());
+        }
+        if paused {
+            // A new generation, so the exiting process isn't taken for a crash
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            self.is_running.store(false, Ordering::SeqCst);
+            self.stop_process().await;
+            info!("rust-analyzer stopped while indexing is paused");
+            Ok(())
+        } else {
+            let result = self.start_instance().await;
+            if let Err(e) = &result {
+                // Let the supervisor retry with backoff
+                self.report_failure(
+                    self.generation.load(Ordering::SeqCst),
+                    format!("Starting rust-analyzer after a pause failed: {e:#}"),
+                );
+            }
+            result
+        }
+    }
+
+    fn report_failure(&self, generation: u64, reason: String) {
+        if let Err(e) = self.failure_tx.send(Failure { generation, reason }) {
+            tracing::error!("Failed to report rust-analyzer failure: {}", e);
+        }
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.last_error
+            .lock()
+            .map(|error| error.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_last_error(&self, error: String) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(error);
+        }
+    }
+
+    fn notify_health(&self) {
+        let health = ServerHealth {
+            project: self.project.root().clone(),
+            is_running: self.is_running.load(Ordering::SeqCst),
+            restart_count: self.restart_count.load(Ordering::SeqCst),
+            last_error: self.last_error(),
+        };
+        if let Err(e) = self.notifier.try_send(LspNotification::ServerHealth(health)) {
+            if matches!(e, flume::TrySendError::Disconnected(_)) {
+                tracing::debug!("Channel closed when sending server health: {}", e);
+            } else {
+                tracing::error!("Failed to send server health: {}", e);
+            }
+        }
+    }
+
+    /// Waits for failures of the running instance and replaces it
+    async fn supervise(self: Arc<Self>, failures: flume::Receiver<Failure>) {
+        let mut attempt = 0;
+        let mut started_at = Instant::now();
+        while let Ok(failure) = failures.recv_async().await {
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            if failure.generation != self.generation.load(Ordering::SeqCst) {
+                debug!("Ignoring failure of a replaced rust-analyzer: {}", failure.reason);
+                continue;
+            }
+            if started_at.elapsed() >= HEALTHY_AFTER {
+                attempt = 0;
+            }
+
+            let mut reason = failure.reason;
+            if let Some(process) = self.process.lock().await.as_mut()
+                && let Ok(Some(status)) = process.child.try_status()
+            {
+                reason = format!("{reason} ({status})");
+            }
+            tracing::warn!(
+                "rust-analyzer for {:?} failed: {reason}",
+                self.project.root()
+            );
+            self.is_running.store(false, Ordering::SeqCst);
+            self.set_last_error(reason);
+            self.notify_health();
+
+            loop {
+                let delay = backoff(attempt);
+                attempt += 1;
+                info!("Restarting rust-analyzer in {}s", delay.as_secs());
+                tokio::time::sleep(delay).await;
+                if self.stopped.load(Ordering::SeqCst) {
+                    return;
+                }
+                match self.restart().await {
+                    Ok(()) => break,
+                    Err(e) => {
+                        tracing::error!("Failed to restart rust-analyzer: {:?}", e);
+                        self.set_last_error(format!("Restart failed: {e:#}"));
+                        self.notify_health();
+                    }
+                }
+            }
+            started_at = Instant::now();
+        }
+    }
+
+    async fn restart(&self) -> Result<()> {
+        self.start_instance().await?;
+        let restart_count = self.restart_count.fetch_add(1, Ordering::SeqCst) + 1;
+        info!("rust-analyzer restarted ({restart_count} restarts)");
+        self.notify_health();
+        Ok(())
+    }
+
+    /// Replaces the current process with a new, initialized instance
+    async fn start_instance(&self) -> Result<()> {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.stop_process().await;
+
+        let (mut server, process) = spawn_server(
+            &self.project,
+            &self.indexed_tx,
+            &self.notifier,
+            &self.progress,
+            &self.failure_tx,
+            generation,
+        )?;
+        // Track the process right away, so a failed initialize doesn't leak it
+        *self.process.lock().await = Some(process);
+        tokio::time::timeout(self.request_timeout, initialize(&mut server, &self.project))
+            .await
+            .context("Initialize request timed out")??;
+
+        for document in self.documents.lock().await.iter() {
+            server
+                .did_open(DidOpenTextDocumentParams {
+                    text_document: document.clone(),
+                })
+                .context("Reopening document failed")?;
+        }
+        *self.server.write().unwrap_or_else(PoisonError::into_inner) = server;
+
+        self.consecutive_timeouts.store(0, Ordering::SeqCst);
+        self.is_running.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn stop_process(&self) {
+        if let Some(mut process) = self.process.lock().await.take() {
+            process.mainloop.abort();
+            if let Err(e) = process.child.kill() {
+                debug!("Failed to kill rust-analyzer: {:?}", e);
+            }
+        }
+    }
+}
+
+/// 1s, 2s, 4s, ... up to `MAX_BACKOFF`
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+/// Starts rust-analyzer and the main loop talking to it. When the main loop
+/// ends, i.e. the process exited, a failure for `generation` is reported.
+fn spawn_server(
+    project: &Project,
+    indexed_tx: &flume::Sender<()>,
+    notifier: &flume::Sender<LspNotification>,
+    progress: &SharedProgress,
+    failure_tx: &flume::Sender<Failure>,
+    generation: u64,
+) -> Result<(ServerSocket, ServerProcess)> {
+    let indexed_tx = indexed_tx.clone();
+    let notifier = notifier.clone();
+    let progress = progress.clone();
+    let project_root = project.root().clone();
+    let settings = project.rust_analyzer.settings.clone();
+    let (mainloop, server) = async_lsp::MainLoop::new_client(|_server| {
+        ServiceBuilder::new()
+            .layer(TracingLayer::default())
+            .layer(LifecycleLayer::default()) // Handle init/shutdown automatically
+            .layer(CatchUnwindLayer::default())
+            .layer(ConcurrencyLayer::default())
+            .service(ClientState::new_router(
+                indexed_tx,
+                notifier,
+                project_root,
+                progress,
+                settings,
+            ))
+    });
+
+    let mut child = spawn_process(project)?;
+    let stdout = child.stdout.take().context("Failed to get stdout")?;
+    let stdin = child.stdin.take().context("Failed to get stdin")?;
+
+    let failure_tx = failure_tx.clone();
+    let mainloop = tokio::spawn(async move {
+        let reason = match mainloop.run_buffered(stdout, stdin).await {
+            Ok(()) => {
+                debug!("LSP mainloop finished gracefully.");
+                "rust-analyzer exited".to_string()
+            }
+            Err(e) => {
+                tracing::error!("LSP mainloop finished with error: {}", e);
+                format!("LSP mainloop failed: {e}")
+            }
+        };
+        // Nobody listens anymore once the supervisor is gone
+        let _ = failure_tx.send(Failure { generation, reason });
+    });
+
+    Ok((server, ServerProcess { child, mainloop }))
+}
+
+fn spawn_process(project: &Project) -> Result<async_process::Child> {
+    let config = &project.rust_analyzer;
+    match async_process::Command::new(config.binary())
+        .args(&config.args)
+        .envs(&config.env)
+        .current_dir(project.root())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(process) => Ok(process),
+        Err(e) if config.binary.is_some() => Err(anyhow::anyhow!(
+            "Failed to run the configured rust-analyzer {:?}: {}",
+            config.binary(),
+            e
+        )),
+        Err(e) => {
+            // First attempt failed, try to locate rust-analyzer in standard paths
+            tracing::warn!("Failed to run rust-analyzer directly: {}", e);
+
+            if cfg!(windows) {
+                // Try to locate rust-analyzer in standard Windows locations
+                let mut rust_analyzer_path = None;
+
+                // Check in .cargo/bin
+                if let Some(home_dir) = dirs::home_dir() {
+                    let cargo_bin_path = home_dir.join(".cargo").join("bin").join("rust-analyzer.exe");
+                    if cargo_bin_path.exists() {
+                        tracing::info!("Found rust-analyzer.exe at: {:?}", cargo_bin_path);
+                        rust_analyzer_path = Some(cargo_bin_path);
+                    }
+                }
+
+                if let Some(path) = rust_analyzer_path {
+                    async_process::Command::new(path)
+                        .args(&config.args)
+                        .envs(&config.env)
+                        .current_dir(project.root())
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::inherit())
+                        .spawn()
+                        .map_err(|e| anyhow::anyhow!(
+                            "Failed to run rust-analyzer from found path: {}. Try manually installing with 'rustup component add rust-analyzer --toolchain stable-x86_64-pc-windows-msvc'", e
+                        ))
+                } else {
+                    Err(anyhow::anyhow!(
+                        "Could not locate rust-analyzer executable. Please run 'rustup component add rust-analyzer --toolchain stable-x86_64-pc-windows-msvc' to install it."
+                    ))
+                }
+            } else {
+                // For non-Windows platforms, just report the original error
+                Err(anyhow::anyhow!(
+                    "Failed to run rust-analyzer: {}. Please make sure rust-analyzer is installed and available in your PATH, or set `rust_analyzer_binary` in the configuration.", e
+                ))
+            }
+        }
+    }
+}
+
+/// Runs the `initialize` handshake with a freshly started rust-analyzer
+async fn initialize(server: &mut ServerSocket, project: &Project) -> Result<()> {
+    let init_ret = server
+        .initialize(InitializeParams {
+            workspace_folders: Some(vec![WorkspaceFolder {
+                uri: project.uri()?,
+                name: "root".into(),
+            }]),
+            capabilities: ClientCapabilities {
+                window: Some(WindowClientCapabilities {
+                    work_done_progress: Some(true), // Required for indexing progress
+                    ..WindowClientCapabilities::default()
+                }),
+                text_document: Some(TextDocumentClientCapabilities {
+                    document_symbol: Some(DocumentSymbolClientCapabilities {
+                        // Flat symbols are easier to process for us
+                        hierarchical_document_symbol_support: Some(false),
+                        ..DocumentSymbolClientCapabilities::default()
+                    }),
+                    hover: Some(HoverClientCapabilities {
+                        content_format: Some(vec![MarkupKind::Markdown]),
+                        ..HoverClientCapabilities::default()
+                    }),
+                    completion: Some(CompletionClientCapabilities {
+                        completion_item: Some(CompletionItemCapability {
+                            // Plain insert texts are easier to read than snippets
+                            snippet_support: Some(false),
+                            documentation_format: Some(vec![MarkupKind::Markdown]),
+                            resolve_support: Some(CompletionItemCapabilityResolveSupport {
+                                properties: vec![
+                                    "documentation".into(),
+                                    "detail".into(),
+                                    "additionalTextEdits".into(),
+                                ],
+                            }),
+                            ..CompletionItemCapability::default()
+                        }),
+                        ..CompletionClientCapabilities::default()
+                    }),
+                    signature_help: Some(SignatureHelpClientCapabilities {
+                        signature_information: Some(SignatureInformationSettings {
+                            documentation_format: Some(vec![MarkupKind::Markdown]),
+                            parameter_information: Some(ParameterInformationSettings {
+                                label_offset_support: Some(true),
+                            }),
+                            active_parameter_support: Some(true),
+                        }),
+                        ..SignatureHelpClientCapabilities::default()
+                    }),
+                    code_action: Some(CodeActionClientCapabilities {
+                        code_action_literal_support: Some(CodeActionLiteralSupport {
+                            code_action_kind: CodeActionKindLiteralSupport {
+                                value_set: ["", "quickfix", "refactor", "refactor.extract",
+                                    "refactor.inline", "refactor.rewrite", "source"]
+                                    .map(String::from)
+                                    .to_vec(),
+                            },
+                        }),
+                        is_preferred_support: Some(true),
+                        disabled_support: Some(true),
+                        data_support: Some(true),
+                        // Edits are computed when an action is applied
+                        resolve_support: Some(CodeActionCapabilityResolveSupport {
+                            properties: vec!["edit".into()],
+                        }),
+                        ..CodeActionClientCapabilities::default()
+                    }),
+                    // Pulled with `textDocument/diagnostic` to find code actions for them
+                    diagnostic: Some(DiagnosticClientCapabilities::default()),
+                    rename: Some(RenameClientCapabilities {
+                        prepare_support: Some(true),
+                        ..RenameClientCapabilities::default()
+                    }),
+                    ..TextDocumentClientCapabilities::default()
+                }),
+                workspace: Some(WorkspaceClientCapabilities {
+                    // Answered from the project settings by `ClientState`
+                    configuration: Some(true),
+                    // Lets renaming a module also rename its file
+                    workspace_edit: Some(WorkspaceEditClientCapabilities {
+                        document_changes: Some(true),
+                        resource_operations: Some(vec![
+                            ResourceOperationKind::Create,
+                            ResourceOperationKind::Rename,
+                            ResourceOperationKind::Delete,
+                        ]),
+                        ..WorkspaceEditClientCapabilities::default()
+                    }),
+                    ..WorkspaceClientCapabilities::default()
+                }),
+                experimental: Some(json!({
+                    "hoverActions": true
+                })),
+                ..ClientCapabilities::default()
+            },
+            initialization_options: project.rust_analyzer.settings.clone(),
+            ..InitializeParams::default()
+        })
+        .await?;
+    tracing::trace!("Initialized: {init_ret:?}");
+    info!("LSP Initialized");
+
+    server
+        .initialized(InitializedParams {})
+        .context("Sending Initialized notification failed")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::IndexingProgress;
+    use crate::project::RustAnalyzerConfig;
+    use async_lsp::router::Router;
+    use futures::channel::mpsc;
+    use futures::{AsyncWrite, TryStreamExt};
+    use lsp_types::request::HoverRequest;
+    use lsp_types::{
+        HoverParams, Position, TextDocumentIdentifier, TextDocumentPositionParams,
+        WorkDoneProgressParams,
+    };
+    use std::pin::Pin;
+    use std::task::Poll;
+
+    /// The writing end of an in-memory pipe
+    struct PipeWriter(mpsc::UnboundedSender<std::io::Result<Vec<u8>>>);
+
+    impl AsyncWrite for PipeWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let result = self
+                .0
+                .unbounded_send(Ok(buf.to_vec()))
+                .map(|_| buf.len())
+                .map_err(|_| std::io::ErrorKind::BrokenPipe.into());
+            Poll::Ready(result)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+            self.0.close_channel();
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn pipe() -> (PipeWriter, impl futures::AsyncRead) {
+        let (tx, rx) = mpsc::unbounded();
+        (PipeWriter(tx), rx.into_async_read())
+    }
+
+    /// Connects a client socket to a fake server whose hover requests take
+    /// 200ms. Returns the socket and the most hovers seen in flight at once.
+    fn fake_server() -> (ServerSocket, Arc<AtomicUsize>) {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let max_clone = max_in_flight.clone();
+        let (server_loop, client) = async_lsp::MainLoop::new_server(|_| {
+            let mut router = Router::new(());
+            router.request::<HoverRequest, _>(move |_, _| {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_clone.clone();
+                async move {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok(None)
+                }
+            });
+            // The main loop runs requests concurrently by itself
+            router
+        });
+        let (client_loop, server) =
+            async_lsp::MainLoop::new_client(|_| Router::<()>::new(()));
+
+        let (client_writer, server_reader) = pipe();
+        let (server_writer, client_reader) = pipe();
+        tokio::spawn(async move {
+            // The main loop ends once its own socket is dropped
+            let _client = client;
+            server_loop.run_buffered(server_reader, server_writer).await
+        });
+        tokio::spawn(client_loop.run_buffered(client_reader, client_writer));
+        (server, max_in_flight)
+    }
+
+    async fn max_overlap(max_concurrent_requests: usize) -> usize {
+        let (server, max_in_flight) = fake_server();
+        let project = Project {
+            root: std::env::temp_dir(),
+            ignore_crates: vec![],
+            docsrs: None,
+            rust_analyzer: RustAnalyzerConfig {
+                max_concurrent_requests: Some(max_concurrent_requests),
+                ..Default::default()
+            },
+        };
+        let (failure_tx, _failure_rx) = flume::unbounded();
+        let supervisor = Supervisor::new(
+            &project,
+            server,
+            None,
+            flume::unbounded().0,
+            flume::unbounded().0,
+            Arc::new(std::sync::Mutex::new(IndexingProgress::new(project.root.clone()))),
+            failure_tx,
+        );
+
+        let hovers = (0..4).map(|line| {
+            let params = HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::parse("file:///project/src/lib.rs").unwrap(),
+                    },
+                    position: Position::new(line, 0),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            };
+            supervisor.request("Hover", async |server| server.hover(params).await)
+        });
+        for result in futures::future::join_all(hovers).await {
+            assert!(result.unwrap().is_none());
+        }
+        max_in_flight.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_requests_overlap() {
+        assert_eq!(max_overlap(8).await, 4);
+        assert_eq!(max_overlap(2).await, 2);
+        assert_eq!(max_overlap(1).await, 1);
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(100), MAX_BACKOFF);
+    }
+}
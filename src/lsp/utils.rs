@@ -1,5 +1,11 @@
-use lsp_types::{GotoDefinitionResponse, MarkedString};
-use std::collections::HashSet;
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, Documentation, GotoDefinitionResponse, InlayHint,
+    InlayHintLabel, MarkedString, NumberOrString, ParameterLabel, Position, Range, SignatureHelp,
+};
+use serde_json::Value;
+
+use super::{MemoryLayoutNode, RecursiveMemoryLayout};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use url::Url;
@@ -54,3 +60,715 @@ fn url_to_file_path(url: &Url) -> Result<PathBuf, std::io::Error> {
         )
     })
 }
+
+/// The byte offset in `line` of an LSP character offset, which counts UTF-16
+/// code units. Offsets past the end of the line map to its end.
+pub fn utf16_to_byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character as usize {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// The LSP position of a 0-based line and either a 1-based `column` in
+/// characters, or the start of the first occurrence of `text` on the line
+pub fn position_in_line(
+    content: &str,
+    line: u32,
+    column: Option<u32>,
+    text: Option<&str>,
+) -> Result<Position, String> {
+    let Some(line_text) = content.lines().nth(line as usize) else {
+        return Err(format!(
+            "Line {} is past the end of the file, which has {} lines",
+            line + 1,
+            content.lines().count()
+        ));
+    };
+    let prefix = match (column, text) {
+        (Some(column), _) => {
+            let end = line_text
+                .char_indices()
+                .nth(column.saturating_sub(1) as usize)
+                .map(|(offset, _)| offset)
+                .unwrap_or(line_text.len());
+            &line_text[..end]
+        }
+        (None, Some(text)) => {
+            let Some(offset) = line_text.find(text) else {
+                return Err(format!("`{text}` not found on line {}: {line_text}", line + 1));
+            };
+            &line_text[..offset]
+        }
+        (None, None) => return Err("Either a column or a text on the line is required".to_string()),
+    };
+    Ok(Position::new(line, prefix.encode_utf16().count() as u32))
+}
+
+/// The range selected by 0-based lines and either a 1-based `column` (an
+/// empty range), a `text` on the first line (the text itself) or nothing
+/// (the whole line). With `end_line`, the range extends to the end of it.
+pub fn selection_range(
+    content: &str,
+    line: u32,
+    end_line: Option<u32>,
+    column: Option<u32>,
+    text: Option<&str>,
+) -> Result<Range, String> {
+    let line_end = |line: u32| {
+        content
+            .lines()
+            .nth(line as usize)
+            .map(|text| Position::new(line, text.encode_utf16().count() as u32))
+            .ok_or_else(|| {
+                format!(
+                    "Line {} is past the end of the file, which has {} lines",
+                    line + 1,
+                    content.lines().count()
+                )
+            })
+    };
+    let mut range = match (column, text) {
+        (None, None) => Range::new(Position::new(line, 0), line_end(line)?),
+        (column, text) => {
+            let start = position_in_line(content, line, column, text)?;
+            let length = match (column, text) {
+                (None, Some(text)) => text.encode_utf16().count() as u32,
+                _ => 0,
+            };
+            Range::new(start, Position::new(line, start.character + length))
+        }
+    };
+    if let Some(end_line) = end_line.filter(|end_line| *end_line > line) {
+        range.end = line_end(end_line)?;
+    }
+    Ok(range)
+}
+
+/// A diagnostic on one line: its 1-based position, severity, message and code
+pub fn format_diagnostic(diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "diagnostic",
+    };
+    let code = match &diagnostic.code {
+        Some(NumberOrString::String(code)) => format!(" [{code}]"),
+        Some(NumberOrString::Number(code)) => format!(" [{code}]"),
+        None => String::new(),
+    };
+    let message = diagnostic.message.lines().next().unwrap_or_default();
+    format!(
+        "{}:{} {severity}{code}: {message}",
+        diagnostic.range.start.line + 1,
+        diagnostic.range.start.character + 1
+    )
+}
+
+/// The byte offset in `content` of an LSP position
+pub fn position_to_byte_offset(content: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        if i == position.line as usize {
+            let line = line.trim_end_matches(['\n', '\r']);
+            return line_start + utf16_to_byte_offset(line, position.character);
+        }
+        line_start += line.len();
+    }
+    content.len()
+}
+
+/// Inserts `text` at `position`, returning the new content and the position
+/// right after the inserted text
+pub fn insert_at(content: &str, position: Position, text: &str) -> (String, Position) {
+    let offset = position_to_byte_offset(content, position);
+    let mut new_content = String::with_capacity(content.len() + text.len());
+    new_content.push_str(&content[..offset]);
+    new_content.push_str(text);
+    new_content.push_str(&content[offset..]);
+
+    let end = match text.rsplit_once('\n') {
+        Some((before, last_line)) => Position::new(
+            position.line + before.matches('\n').count() as u32 + 1,
+            last_line.encode_utf16().count() as u32,
+        ),
+        None => Position::new(
+            position.line,
+            position.character + text.encode_utf16().count() as u32,
+        ),
+    };
+    (new_content, end)
+}
+
+/// The text of a documentation string or markup
+pub fn documentation_text(documentation: &Documentation) -> &str {
+    match documentation {
+        Documentation::String(text) => text,
+        Documentation::MarkupContent(markup) => &markup.value,
+    }
+}
+
+/// All candidate signatures of a call, the active one first, each with its
+/// active parameter and the documentation of its parameters
+pub fn format_signature_help(help: &SignatureHelp) -> String {
+    let active_signature = help.active_signature.unwrap_or(0) as usize;
+    let mut order: Vec<usize> = (0..help.signatures.len()).collect();
+    order.sort_by_key(|i| *i != active_signature);
+
+    let mut sections = Vec::new();
+    for i in order {
+        let signature = &help.signatures[i];
+        let mut section = if i == active_signature && help.signatures.len() > 1 {
+            format!("Active signature:\n```rust\n{}\n```", signature.label)
+        } else {
+            format!("```rust\n{}\n```", signature.label)
+        };
+        let active_parameter = signature
+            .active_parameter
+            .or(help.active_parameter)
+            .map(|p| p as usize);
+        for (index, parameter) in signature.parameters.iter().flatten().enumerate() {
+            let label = match &parameter.label {
+                ParameterLabel::Simple(label) => label.as_str(),
+                ParameterLabel::LabelOffsets([start, end]) => {
+                    let start = utf16_to_byte_offset(&signature.label, *start);
+                    let end = utf16_to_byte_offset(&signature.label, *end);
+                    signature.label.get(start..end).unwrap_or_default()
+                }
+            };
+            let marker = if Some(index) == active_parameter {
+                " (active)"
+            } else {
+                ""
+            };
+            section.push_str(&format!("\n- `{label}`{marker}"));
+            if let Some(documentation) = &parameter.documentation {
+                section.push_str(&format!(": {}", documentation_text(documentation)));
+            }
+        }
+        if let Some(documentation) = &signature.documentation {
+            section.push_str(&format!("\n\n{}", documentation_text(documentation)));
+        }
+        sections.push(section);
+    }
+    sections.join("\n\n---\n\n")
+}
+
+/// The text of an inlay hint, with the padding an editor would show
+fn inlay_hint_text(hint: &InlayHint) -> String {
+    let label = match &hint.label {
+        InlayHintLabel::String(label) => label.clone(),
+        InlayHintLabel::LabelParts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+    };
+    let left = if hint.padding_left == Some(true) { " " } else { "" };
+    let right = if hint.padding_right == Some(true) { " " } else { "" };
+    format!("{left}«{label}»{right}")
+}
+
+/// Lines `start_line..=end_line` (0-based) of `content`, prefixed with their
+/// 1-based line numbers, with the inlay hints inserted where an editor shows them
+pub fn render_inlay_hints(
+    content: &str,
+    start_line: u32,
+    end_line: u32,
+    hints: &[InlayHint],
+) -> String {
+    content
+        .lines()
+        .enumerate()
+        .skip(start_line as usize)
+        .take_while(|(i, _)| *i <= end_line as usize)
+        .map(|(i, line)| {
+            let mut line_hints: Vec<_> = hints
+                .iter()
+                .filter(|hint| hint.position.line as usize == i)
+                .map(|hint| (utf16_to_byte_offset(line, hint.position.character), hint))
+                .collect();
+            // Stable, so hints at the same position keep their order
+            line_hints.sort_by_key(|(offset, _)| *offset);
+            let mut rendered = String::with_capacity(line.len());
+            let mut last = 0;
+            for (offset, hint) in line_hints {
+                rendered.push_str(&line[last..offset]);
+                rendered.push_str(&inlay_hint_text(hint));
+                last = offset;
+            }
+            rendered.push_str(&line[last..]);
+            format!("{:>5} {rendered}", i + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The syntax tree as indented `KIND@start..end` lines with the text of
+/// tokens. Trees in rust-analyzer's JSON format are converted, text is
+/// returned as it is.
+pub fn syntax_tree_text(tree: &str) -> String {
+    fn render(node: &Value, depth: usize, out: &mut String) {
+        let kind = node.get("kind").and_then(Value::as_str).unwrap_or("?");
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(kind);
+        // Byte offsets, like in the text format
+        if let (Some(start), Some(end)) = (
+            node.get("istart").and_then(Value::as_u64),
+            node.get("iend").and_then(Value::as_u64),
+        ) {
+            out.push_str(&format!("@{start}..{end}"));
+        }
+        if let Some(text) = node.get("text").and_then(Value::as_str) {
+            out.push_str(&format!(" {text:?}"));
+        }
+        out.push('\n');
+        for child in node
+            .get("children")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            render(child, depth + 1, out);
+        }
+    }
+
+    match serde_json::from_str::<Value>(tree) {
+        Ok(node) if node.get("kind").is_some() => {
+            let mut out = String::new();
+            render(&node, 0, &mut out);
+            out
+        }
+        _ => tree.to_string(),
+    }
+}
+
+/// The part of a syntax tree in text form for the top-level item containing
+/// the byte `offset`. The whole tree if no item contains it.
+pub fn syntax_subtree(tree: &str, offset: usize) -> &str {
+    let depth = |line: &str| line.len() - line.trim_start().len();
+    let contains = |line: &str| {
+        let range = line.trim().split(' ').next().and_then(|node| node.split_once('@'));
+        range
+            .and_then(|(_, range)| range.split_once(".."))
+            .and_then(|(start, end)| Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)))
+            .is_some_and(|(start, end)| start <= offset && offset < end)
+    };
+    // Items are the children of the root node, on the second line and after
+    let item_depth = tree.lines().nth(1).map(depth).unwrap_or_default();
+
+    let mut start = None;
+    let mut line_start = 0;
+    for line in tree.split_inclusive('\n') {
+        match start {
+            None if line_start > 0 && depth(line) == item_depth && contains(line) => {
+                start = Some(line_start);
+            }
+            Some(start) if depth(line) <= item_depth => return &tree[start..line_start],
+            _ => (),
+        }
+        line_start += line.len();
+    }
+    start.map_or(tree, |start| &tree[start..])
+}
+
+/// Bytes per cache line on common hardware
+const CACHE_LINE: u64 = 64;
+
+/// A table of the fields of a type sorted by offset, with the padding after
+/// each. Fields of fields are included up to `depth` levels, with offsets
+/// from the start of the type.
+pub fn format_memory_layout(layout: &RecursiveMemoryLayout, depth: usize) -> String {
+    fn children(layout: &RecursiveMemoryLayout, node: &MemoryLayoutNode) -> Vec<MemoryLayoutNode> {
+        let Ok(start) = usize::try_from(node.children_start) else {
+            return Vec::new();
+        };
+        let end = (start + node.children_len as usize).min(layout.nodes.len());
+        let mut children = layout.nodes.get(start..end).unwrap_or_default().to_vec();
+        // Fields are reordered by the compiler, so declaration order says little
+        children.sort_by_key(|child| child.offset);
+        children
+    }
+
+    fn rows(
+        layout: &RecursiveMemoryLayout,
+        parent: &MemoryLayoutNode,
+        prefix: &str,
+        base: u64,
+        depth: usize,
+        out: &mut Vec<[String; 6]>,
+    ) -> u64 {
+        let fields = children(layout, parent);
+        let mut padding_total = 0;
+        for (i, field) in fields.iter().enumerate() {
+            let end = fields
+                .get(i + 1)
+                .map_or(parent.size, |next| next.offset);
+            let padding = end.saturating_sub(field.offset + field.size);
+            padding_total += padding;
+            let name = format!("{prefix}{}", field.item_name);
+            out.push([
+                name.clone(),
+                field.typename.clone(),
+                (base + field.offset).to_string(),
+                field.size.to_string(),
+                field.alignment.to_string(),
+                padding.to_string(),
+            ]);
+            if depth > 1 {
+                padding_total += rows(
+                    layout,
+                    field,
+                    &format!("{name}."),
+                    base + field.offset,
+                    depth - 1,
+                    out,
+                );
+            }
+        }
+        padding_total
+    }
+
+    let Some(root) = layout.nodes.first() else {
+        return "No layout".to_string();
+    };
+    let mut table = Vec::new();
+    rows(layout, root, "", 0, depth.max(1), &mut table);
+    // Padding inside fields counts too, however deep they are shown
+    let padding = rows(layout, root, "", 0, usize::MAX, &mut Vec::new());
+    let mut out = format!(
+        "`{}`: size {} bytes, alignment {}, {} bytes of padding",
+        root.typename, root.size, root.alignment, padding
+    );
+    if root.size > CACHE_LINE {
+        out.push_str(&format!(
+            ", spans {} cache lines of {CACHE_LINE} bytes",
+            root.size.div_ceil(CACHE_LINE)
+        ));
+    }
+    if table.is_empty() {
+        return out;
+    }
+
+    let header = ["field", "type", "offset", "size", "align", "padding after"];
+    out.push_str(&format!("\n\n| {} |\n|{}\n", header.join(" | "), "---|".repeat(6)));
+    for row in table {
+        out.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    out
+}
+
+/// The dependencies of each crate in a DOT crate graph, by crate name.
+/// Crates that appear several times, e.g. in two versions, are numbered.
+pub fn crate_graph_adjacency(dot: &str) -> BTreeMap<String, Vec<String>> {
+    let mut names: Vec<(&str, &str)> = Vec::new();
+    let mut edges: Vec<(&str, &str)> = Vec::new();
+    for line in dot.lines().map(str::trim) {
+        let statement = line.trim_end_matches(';');
+        if let Some((from, rest)) = statement.split_once("->") {
+            let to = rest.trim().split(['[', ' ']).next().unwrap_or_default();
+            edges.push((from.trim(), to));
+        } else if let Some((id, attributes)) = statement.split_once('[')
+            && let Some(label) = attributes
+                .split_once("label=\"")
+                .and_then(|(_, label)| label.split_once('"'))
+                .map(|(label, _)| label)
+        {
+            names.push((id.trim(), label));
+        }
+    }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, name) in &names {
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let unique: HashMap<&str, String> = names
+        .iter()
+        .map(|(id, name)| {
+            let unique = if counts[name] > 1 {
+                let n = seen.entry(name).or_default();
+                *n += 1;
+                format!("{name} ({n})")
+            } else {
+                name.to_string()
+            };
+            (*id, unique)
+        })
+        .collect();
+
+    let mut graph: BTreeMap<String, Vec<String>> =
+        unique.values().map(|name| (name.clone(), Vec::new())).collect();
+    for (from, to) in edges {
+        if let (Some(from), Some(to)) = (unique.get(from), unique.get(to)) {
+            graph.entry(from.clone()).or_default().push(to.clone());
+        }
+    }
+    for dependencies in graph.values_mut() {
+        dependencies.sort();
+        dependencies.dedup();
+    }
+    graph
+}
+
+/// The adjacency list as a JSON object with one crate per line. Crates are
+/// only added while the object stays within `max_bytes`, so the JSON is
+/// always valid. Returns it with the number of crates included.
+pub fn crate_graph_json(
+    graph: &BTreeMap<String, Vec<String>>,
+    max_bytes: usize,
+) -> (String, usize) {
+    let mut json = String::from("{");
+    let mut included = 0;
+    for (name, dependencies) in graph {
+        let entry = format!(
+            "\n  {}: {}",
+            serde_json::Value::from(name.as_str()),
+            serde_json::Value::from(dependencies.clone())
+        );
+        // The separating comma and the closing brace
+        if json.len() + entry.len() + 3 > max_bytes {
+            break;
+        }
+        if included > 0 {
+            json.push(',');
+        }
+        json.push_str(&entry);
+        included += 1;
+    }
+    json.push_str(if included > 0 { "\n}" } else { "}" });
+    (json, included)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{InlayHintKind, Position};
+
+    fn hint(line: u32, character: u32, label: &str, kind: InlayHintKind) -> InlayHint {
+        InlayHint {
+            position: Position::new(line, character),
+            label: InlayHintLabel::String(label.to_string()),
+            kind: Some(kind),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: Some(kind == InlayHintKind::PARAMETER),
+            data: None,
+        }
+    }
+
+    #[test]
+    fn test_utf16_to_byte_offset() {
+        assert_eq!(utf16_to_byte_offset("let a = 1;", 4), 4);
+        // `é` is one UTF-16 unit but two bytes, `🦀` two units and four bytes
+        assert_eq!(utf16_to_byte_offset("é🦀x", 1), 2);
+        assert_eq!(utf16_to_byte_offset("é🦀x", 3), 6);
+        assert_eq!(utf16_to_byte_offset("é🦀x", 10), 7);
+    }
+
+    #[test]
+    fn test_position_in_line() {
+        let content = "fn a() {}\n    let é = b(1, 2);";
+        assert_eq!(position_in_line(content, 1, Some(5), None), Ok(Position::new(1, 4)));
+        assert_eq!(position_in_line(content, 1, None, Some("1, 2")), Ok(Position::new(1, 14)));
+        // Columns are in characters, LSP positions in UTF-16 units
+        assert_eq!(position_in_line("é🦀x", 0, Some(3), None), Ok(Position::new(0, 3)));
+        assert!(position_in_line(content, 1, None, Some("c(")).is_err());
+        assert!(position_in_line(content, 2, Some(1), None).is_err());
+        assert!(position_in_line(content, 0, None, None).is_err());
+    }
+
+    #[test]
+    fn test_selection_range() {
+        let content = "fn a() {\n    let x = b(1);\n}";
+        assert_eq!(
+            selection_range(content, 1, None, None, None),
+            Ok(Range::new(Position::new(1, 0), Position::new(1, 17)))
+        );
+        assert_eq!(
+            selection_range(content, 1, None, None, Some("b(1)")),
+            Ok(Range::new(Position::new(1, 12), Position::new(1, 16)))
+        );
+        assert_eq!(
+            selection_range(content, 1, None, Some(5), None),
+            Ok(Range::new(Position::new(1, 4), Position::new(1, 4)))
+        );
+        assert_eq!(
+            selection_range(content, 0, Some(2), Some(1), None),
+            Ok(Range::new(Position::new(0, 0), Position::new(2, 1)))
+        );
+        assert!(selection_range(content, 0, Some(3), None, None).is_err());
+    }
+
+    #[test]
+    fn test_format_diagnostic() {
+        let mut diagnostic = Diagnostic::new_simple(
+            Range::new(Position::new(4, 8), Position::new(4, 12)),
+            "missing match arm: `None` not covered\nsecond line".to_string(),
+        );
+        diagnostic.severity = Some(DiagnosticSeverity::ERROR);
+        diagnostic.code = Some(NumberOrString::String("E0004".to_string()));
+        assert_eq!(
+            format_diagnostic(&diagnostic),
+            "5:9 error [E0004]: missing match arm: `None` not covered"
+        );
+    }
+
+    #[test]
+    fn test_syntax_tree() {
+        let json = r#"{"kind":"SOURCE_FILE","istart":0,"iend":22,"children":[
+            {"kind":"FN","istart":0,"iend":11,"children":[
+                {"kind":"FN_KW","istart":0,"iend":2,"text":"fn"}]},
+            {"kind":"WHITESPACE","istart":11,"iend":12,"text":"\n"},
+            {"kind":"STRUCT","istart":12,"iend":22,"children":[
+                {"kind":"STRUCT_KW","istart":12,"iend":18,"text":"struct"}]}]}"#;
+        let tree = syntax_tree_text(json);
+        assert_eq!(
+            tree,
+            "SOURCE_FILE@0..22\n  FN@0..11\n    FN_KW@0..2 \"fn\"\n  WHITESPACE@11..12 \"\\n\"\n  STRUCT@12..22\n    STRUCT_KW@12..18 \"struct\"\n"
+        );
+        // The text format is kept
+        assert_eq!(syntax_tree_text("SOURCE_FILE@0..0\n"), "SOURCE_FILE@0..0\n");
+
+        assert_eq!(syntax_subtree(&tree, 1), "  FN@0..11\n    FN_KW@0..2 \"fn\"\n");
+        assert_eq!(syntax_subtree(&tree, 15), "  STRUCT@12..22\n    STRUCT_KW@12..18 \"struct\"\n");
+        assert_eq!(syntax_subtree(&tree, 100), tree);
+    }
+
+    #[test]
+    fn test_format_memory_layout() {
+        let node = |item_name: &str, typename: &str, size, offset, alignment, parent_idx, children: (i64, u64)| {
+            MemoryLayoutNode {
+                item_name: item_name.to_string(),
+                typename: typename.to_string(),
+                size,
+                offset,
+                alignment,
+                parent_idx,
+                children_start: children.0,
+                children_len: children.1,
+            }
+        };
+        let layout = RecursiveMemoryLayout {
+            nodes: vec![
+                node("Packet", "Packet", 24, 0, 8, -1, (1, 3)),
+                node("flag", "bool", 1, 16, 1, 0, (-1, 0)),
+                node("id", "u64", 8, 0, 8, 0, (-1, 0)),
+                node("pos", "Pos", 6, 8, 2, 0, (4, 2)),
+                node("x", "u16", 2, 0, 2, 3, (-1, 0)),
+                node("y", "u32", 4, 2, 2, 3, (-1, 0)),
+            ],
+        };
+        assert_eq!(
+            format_memory_layout(&layout, 1),
+            "`Packet`: size 24 bytes, alignment 8, 9 bytes of padding\n\n\
+             | field | type | offset | size | align | padding after |\n|---|---|---|---|---|---|\n\
+             | id | u64 | 0 | 8 | 8 | 0 |\n\
+             | pos | Pos | 8 | 6 | 2 | 2 |\n\
+             | flag | bool | 16 | 1 | 1 | 7 |\n"
+        );
+        assert!(format_memory_layout(&layout, 2).contains("| pos.y | u32 | 10 | 4 | 2 | 0 |\n"));
+    }
+
+    #[test]
+    fn test_crate_graph_adjacency() {
+        let dot = r#"digraph rust_analyzer_crate_graph {
+    _0[label="core"][shape="box"];
+    _1[label="alloc"][shape="box"];
+    _2[label="app"][shape="box"];
+    _3[label="syn"][shape="box"];
+    _4[label="syn"][shape="box"];
+    _1 -> _0[label=""];
+    _2 -> _1[label=""];
+    _2 -> _3[label=""];
+    _2 -> _4[label=""];
+}"#;
+        let graph = crate_graph_adjacency(dot);
+        assert_eq!(
+            serde_json::to_string(&graph).unwrap(),
+            r#"{"alloc":["core"],"app":["alloc","syn (1)","syn (2)"],"core":[],"syn (1)":[],"syn (2)":[]}"#
+        );
+
+        let (json, included) = crate_graph_json(&graph, 1000);
+        assert_eq!(included, 5);
+        assert_eq!(serde_json::from_str::<BTreeMap<String, Vec<String>>>(&json).unwrap(), graph);
+        let (json, included) = crate_graph_json(&graph, 50);
+        assert_eq!(json, "{\n  \"alloc\": [\"core\"]\n}");
+        assert_eq!(included, 1);
+        assert!(serde_json::from_str::<serde_json::Value>(&json).is_ok());
+        assert_eq!(crate_graph_json(&graph, 10), ("{}".to_string(), 0));
+    }
+
+    #[test]
+    fn test_insert_at() {
+        let content = "fn a() {\r\n    let v = vec![1];\r\n    v\r\n}";
+        assert_eq!(
+            insert_at(content, Position::new(2, 5), ".iter()."),
+            (
+                "fn a() {\r\n    let v = vec![1];\r\n    v.iter().\r\n}".to_string(),
+                Position::new(2, 13)
+            )
+        );
+        assert_eq!(
+            insert_at(content, Position::new(2, 4), "let é = 1;\n    é."),
+            (
+                "fn a() {\r\n    let v = vec![1];\r\n    let é = 1;\n    é.v\r\n}".to_string(),
+                Position::new(3, 6)
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_signature_help() {
+        use lsp_types::{ParameterInformation, SignatureInformation};
+        let signature = |label: &str, parameters: Vec<ParameterLabel>| SignatureInformation {
+            label: label.to_string(),
+            documentation: None,
+            parameters: Some(
+                parameters
+                    .into_iter()
+                    .map(|label| ParameterInformation {
+                        label,
+                        documentation: None,
+                    })
+                    .collect(),
+            ),
+            active_parameter: None,
+        };
+        let help = SignatureHelp {
+            signatures: vec![
+                signature("fn new() -> Self", vec![]),
+                signature(
+                    "fn timeout(self, é: u8, d: Duration) -> Self",
+                    vec![
+                        ParameterLabel::LabelOffsets([17, 22]),
+                        ParameterLabel::Simple("d: Duration".to_string()),
+                    ],
+                ),
+            ],
+            active_signature: Some(1),
+            active_parameter: Some(1),
+        };
+        assert_eq!(
+            format_signature_help(&help),
+            "Active signature:\n```rust\nfn timeout(self, é: u8, d: Duration) -> Self\n```\n- `é: u8`\n- `d: Duration` (active)\n\n---\n\n```rust\nfn new() -> Self\n```"
+        );
+    }
+
+    #[test]
+    fn test_render_inlay_hints() {
+        let content = "fn main() {\n    let s = \"🦀\".len();\n    f(s);\n}";
+        let hints = [
+            hint(1, 9, ": usize", InlayHintKind::TYPE),
+            hint(2, 6, "count:", InlayHintKind::PARAMETER),
+            hint(0, 0, "unused", InlayHintKind::TYPE),
+        ];
+        assert_eq!(
+            render_inlay_hints(content, 1, 2, &hints),
+            "    2     let s«: usize» = \"🦀\".len();\n    3     f(«count:» s);"
+        );
+    }
+}
@@ -4,6 +4,8 @@ mod docs;
 mod lsp;
 mod mcp;
 mod project;
+#[cfg(test)]
+mod test_utils;
 mod ui;
 
 use std::env::args;
@@ -1,7 +1,11 @@
 use std::sync::Arc;
 
-use crate::context::{Context, ProjectContext};
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::{Runnable, RunnableKind},
+};
 use anyhow::Result;
+use lsp_types::Position;
 use mcp_core::{
     tools::ToolHandlerFn,
     types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
@@ -20,7 +24,7 @@ impl CargoTest {
         Tool {
             name: "cargo_test".to_string(),
             description: Some(
-                "Run the cargo test command in this project. Returns the response in JSON format"
+                "Run the cargo test command in this project. Returns the response in JSON format. To run exactly one test, test module or doctest, pass a source file with the line of the test, or with the label of a runnable from the runnables tool; the exact cargo invocation rust-analyzer computes for it is run."
                     .to_string(),
             ),
             input_schema: json!({
@@ -32,7 +36,15 @@ impl CargoTest {
                     },
                     "file": {
                         "type": "string",
-                        "description": "The absolute path to the `Cargo.toml` file of the project to check"
+                        "description": "The absolute path to the `Cargo.toml` file of the project to check, or to the source file of the test to run with line or runnable"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "Run the innermost test or test module at this line of the file (1 based)"
+                    },
+                    "runnable": {
+                        "type": "string",
+                        "description": "Run the runnable of the file with this label, as listed by the runnables tool, e.g. `test tests::parses`"
                     },
                     "backtrace": {
                         "type": "boolean",
@@ -83,7 +95,7 @@ impl CargoTest {
 
 async fn handle_request(
     project: Arc<ProjectContext>,
-    _relative_file: &str,
+    relative_file: &str,
     request: &CallToolRequest,
 ) -> Result<CallToolResponse, CallToolResponse> {
     let test = request
@@ -100,11 +112,41 @@ async fn handle_request(
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
 
-    let messages: Vec<String> = project
-        .cargo_remote
-        .test(test, backtrace)
-        .await
-        .map_err(|e| error_response(&format!("{e:?}")))?;
+    let label = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("runnable"))
+        .and_then(|v| v.as_str());
+    let line = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("line"))
+        .and_then(|v| v.as_u64());
+
+    let messages: Vec<String> = if label.is_some() || line.is_some() {
+        let runnable = find_runnable(&project, relative_file, label, line).await?;
+        let mut messages = vec![format!("Running `{}`", runnable.command_line())];
+        messages.extend(
+            project
+                .cargo_remote
+                .run_invocation(
+                    runnable.args.override_cargo.as_deref(),
+                    &runnable.args.cargo_command(),
+                    runnable.args.cwd.as_deref(),
+                    &runnable.args.environment,
+                    backtrace,
+                )
+                .await
+                .map_err(|e| error_response(&format!("{e:?}")))?,
+        );
+        messages
+    } else {
+        project
+            .cargo_remote
+            .test(test, backtrace)
+            .await
+            .map_err(|e| error_response(&format!("{e:?}")))?
+    };
 
     Ok(CallToolResponse {
         content: vec![ToolResponseContent::Text {
@@ -114,3 +156,73 @@ async fn handle_request(
         meta: None,
     })
 }
+
+/// The test runnable of a file with the given label, or the innermost one
+/// around the 1-based `line`
+async fn find_runnable(
+    project: &ProjectContext,
+    relative_file: &str,
+    label: Option<&str>,
+    line: Option<u64>,
+) -> Result<Runnable, CallToolResponse> {
+    let position = match (label, line) {
+        (None, Some(line)) => {
+            let content = project
+                .lsp
+                .document_text(relative_file)
+                .await
+                .map_err(|e| error_response(&format!("{e:#}")))?;
+            let line = line.saturating_sub(1) as u32;
+            // Inside the item rather than before it
+            let indent = content
+                .lines()
+                .nth(line as usize)
+                .map(|text| text.len() - text.trim_start().len())
+                .unwrap_or_default();
+            Some(Position::new(line, indent as u32))
+        }
+        _ => None,
+    };
+    let runnables = project
+        .lsp
+        .runnables(relative_file, position)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?;
+    let runnable = match label {
+        Some(label) => runnables
+            .into_iter()
+            .find(|runnable| runnable.label == label)
+            .ok_or_else(|| {
+                error_response(&format!(
+                    "No runnable `{label}` in {relative_file}. The runnables tool lists them"
+                ))
+            })?,
+        None => runnables
+            .into_iter()
+            .filter(|runnable| runnable.location.is_some())
+            .min_by_key(|runnable| {
+                let range = runnable.location.as_ref().map(|l| l.target_range);
+                range.map(|range| (range.end.line - range.start.line, range.end.character))
+            })
+            .ok_or_else(|| {
+                error_response(&format!(
+                    "No test at line {} of {relative_file}",
+                    line.unwrap_or_default()
+                ))
+            })?,
+    };
+
+    let is_test = runnable
+        .args
+        .cargo_args
+        .first()
+        .is_some_and(|command| command == "test" || command == "bench");
+    if runnable.kind != RunnableKind::Cargo || !is_test {
+        return Err(error_response(&format!(
+            "`{}` is not a test or bench, only those can be run: {}",
+            runnable.label,
+            runnable.command_line()
+        )));
+    }
+    Ok(runnable)
+}
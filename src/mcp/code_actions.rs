@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::{format_diagnostic, selection_range},
+};
+use anyhow::Result;
+use lsp_types::{CodeActionOrCommand, Diagnostic, Range};
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::{Value, json};
+
+use super::{
+    McpNotification,
+    utils::{edit_response, error_response, get_info_from_request},
+};
+
+pub struct CodeActions;
+
+impl CodeActions {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "code_actions".to_string(),
+            description: Some(
+                "List the quick fixes and assists rust-analyzer offers for a line, a selection or a diagnostic (fill match arms, add missing impl members, extract function or variable, generate getters, import a missing path, ...) and apply one. Call it without action to list them, then with action to preview the change as a diff, and with apply set to true to write it.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The (first) line of the selection (1 based)"
+                    },
+                    "end_line": {
+                        "type": "number",
+                        "description": "The last line of a selection spanning several lines, e.g. the statements to extract into a function (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "A column on the line to get the actions at (1 based). Without column and text, the whole line is selected"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: text on the line to select, e.g. the expression to extract into a variable"
+                    },
+                    "diagnostic": {
+                        "type": "string",
+                        "description": "Part of the message or code of a diagnostic in the selection, to get the fixes for that diagnostic"
+                    },
+                    "action": {
+                        "type": ["number", "string"],
+                        "description": "The number or title of the action to apply, as listed by a previous call"
+                    },
+                    "apply": {
+                        "type": "boolean",
+                        "description": "Write the changes of the action to disk instead of only previewing them (default false)"
+                    }
+                },
+                "required": ["file", "line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let argument = |name: &str| request.arguments.as_ref().and_then(|args| args.get(name));
+    let line = argument("line")
+        .and_then(|v| v.as_u64())
+        .filter(|line| *line > 0)
+        .ok_or_else(|| error_response("Line is required and 1 based"))?;
+    let end_line = argument("end_line")
+        .and_then(|v| v.as_u64())
+        .filter(|line| *line > 0);
+    let content = project
+        .lsp
+        .document_text(relative_file)
+        .await
+        .map_err(|e| error_response(&format!("{e:#}")))?;
+    let mut range = selection_range(
+        &content,
+        (line - 1) as u32,
+        end_line.map(|line| (line - 1) as u32),
+        argument("column").and_then(|v| v.as_u64()).map(|c| c as u32),
+        argument("text").and_then(|v| v.as_str()),
+    )
+    .map_err(|e| error_response(&e))?;
+
+    // Fixes for diagnostics are only offered if the diagnostics are passed along
+    let diagnostics = match project.lsp.diagnostics(relative_file).await {
+        Ok(diagnostics) => diagnostics,
+        Err(e) => {
+            tracing::debug!("Failed to get diagnostics for {}: {:?}", relative_file, e);
+            Vec::new()
+        }
+    };
+    let mut diagnostics: Vec<Diagnostic> = diagnostics
+        .into_iter()
+        .filter(|diagnostic| overlaps(&diagnostic.range, &range))
+        .collect();
+    if let Some(filter) = argument("diagnostic").and_then(|v| v.as_str()) {
+        let filter = filter.to_lowercase();
+        let Some(diagnostic) = diagnostics.iter().find(|diagnostic| {
+            format_diagnostic(diagnostic).to_lowercase().contains(&filter)
+        }) else {
+            return Err(error_response(&format!(
+                "No diagnostic matching `{filter}` in the selection. Diagnostics there:\n{}",
+                diagnostic_list(&diagnostics)
+            )));
+        };
+        range = diagnostic.range;
+        diagnostics = vec![diagnostic.clone()];
+    }
+
+    let actions = project
+        .lsp
+        .code_actions(relative_file, range, diagnostics.clone())
+        .await
+        .map_err(|e| error_response(&format!("{e:#}")))?;
+
+    let Some(selected) = argument("action") else {
+        return Ok(CallToolResponse {
+            content: vec![ToolResponseContent::Text {
+                text: list_actions(&actions, &diagnostics, &range),
+            }],
+            is_error: None,
+            meta: None,
+        });
+    };
+    let action = match select_action(&actions, selected)? {
+        CodeActionOrCommand::CodeAction(action) => action.clone(),
+        CodeActionOrCommand::Command(command) => {
+            return Err(error_response(&format!(
+                "`{}` runs the editor command `{}` and has no edit that could be applied",
+                command.title, command.command
+            )));
+        }
+    };
+    if let Some(disabled) = &action.disabled {
+        return Err(error_response(&format!(
+            "`{}` is not available here: {}",
+            action.title, disabled.reason
+        )));
+    }
+    let title = action.title.clone();
+    let action = project
+        .lsp
+        .resolve_code_action(action)
+        .await
+        .map_err(|e| error_response(&format!("Failed to resolve `{title}`: {e:#}")))?;
+    let Some(edit) = &action.edit else {
+        return Err(error_response(&format!(
+            "`{title}` has no edit that could be applied"
+        )));
+    };
+    let apply = argument("apply").and_then(|v| v.as_bool()).unwrap_or(false);
+    edit_response(&project, edit, apply, &format!("`{title}`")).await
+}
+
+/// Whether two ranges share a line, so a diagnostic touching the selection counts
+fn overlaps(a: &Range, b: &Range) -> bool {
+    a.start.line <= b.end.line && b.start.line <= a.end.line
+}
+
+fn diagnostic_list(diagnostics: &[Diagnostic]) -> String {
+    if diagnostics.is_empty() {
+        return "(none)".to_string();
+    }
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("- {}", format_diagnostic(diagnostic)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn action_title(action: &CodeActionOrCommand) -> &str {
+    match action {
+        CodeActionOrCommand::CodeAction(action) => &action.title,
+        CodeActionOrCommand::Command(command) => &command.title,
+    }
+}
+
+/// The action with the given 1-based number, the same title or the only
+/// title containing it
+fn select_action<'a>(
+    actions: &'a [CodeActionOrCommand],
+    selected: &Value,
+) -> Result<&'a CodeActionOrCommand, CallToolResponse> {
+    if let Some(number) = selected.as_u64() {
+        return (number as usize)
+            .checked_sub(1)
+            .and_then(|index| actions.get(index))
+            .ok_or_else(|| {
+                error_response(&format!(
+                    "There is no action {number}, there are {} actions",
+                    actions.len()
+                ))
+            });
+    }
+    let title = selected
+        .as_str()
+        .ok_or_else(|| error_response("action has to be a number or a title"))?
+        .to_lowercase();
+    if let Some(action) = actions
+        .iter()
+        .find(|action| action_title(action).to_lowercase() == title)
+    {
+        return Ok(action);
+    }
+    let matching: Vec<_> = actions
+        .iter()
+        .filter(|action| action_title(action).to_lowercase().contains(&title))
+        .collect();
+    match matching.as_slice() {
+        [action] => Ok(action),
+        [] => Err(error_response(&format!("No action matches `{title}`"))),
+        _ => Err(error_response(&format!(
+            "Several actions match `{title}`: {}. Use its number instead",
+            matching
+                .iter()
+                .map(|action| format!("`{}`", action_title(action)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+fn list_actions(
+    actions: &[CodeActionOrCommand],
+    diagnostics: &[Diagnostic],
+    range: &Range,
+) -> String {
+    let mut text = format!(
+        "Diagnostics in the selection:\n{}\n\n",
+        diagnostic_list(diagnostics)
+    );
+    let lines = if range.start.line == range.end.line {
+        format!("line {}", range.start.line + 1)
+    } else {
+        format!("lines {}-{}", range.start.line + 1, range.end.line + 1)
+    };
+    if actions.is_empty() {
+        text.push_str(&format!("No code actions available for {lines}"));
+        return text;
+    }
+    text.push_str(&format!("Code actions for {lines}:\n"));
+    for (i, action) in actions.iter().enumerate() {
+        text.push_str(&format!("{}. {}", i + 1, action_title(action)));
+        if let CodeActionOrCommand::CodeAction(action) = action {
+            if let Some(kind) = &action.kind {
+                text.push_str(&format!(" [{}]", kind.as_str()));
+            }
+            if action.is_preferred == Some(true) {
+                text.push_str(" (preferred)");
+            }
+            if let Some(disabled) = &action.disabled {
+                text.push_str(&format!(" (disabled: {})", disabled.reason));
+            }
+        }
+        text.push('\n');
+    }
+    text.push_str("\nPreview one by calling again with action set to its number or title");
+    text
+}
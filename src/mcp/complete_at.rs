@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::documentation_text,
+};
+use anyhow::Result;
+use lsp_types::CompletionItem;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, get_position},
+};
+
+const DEFAULT_LIMIT: u64 = 50;
+const MAX_LIMIT: u64 = 200;
+/// Documentation longer than this is cut off
+const MAX_DOCS_CHARS: usize = 300;
+
+pub struct CompleteAt;
+
+impl CompleteAt {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "complete_at".to_string(),
+            description: Some(
+                "List the completions rust-analyzer offers at a position, ranked like in an editor, with their kind, signature, docs and the import they would add. Use `insert` to type something first without changing the file, e.g. `.` after a value to list its methods, or `HashM` to find what can be imported.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line to complete at (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column to complete at (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: text on the line. Completion happens at its start"
+                    },
+                    "insert": {
+                        "type": "string",
+                        "description": "Text to insert at the position before completing, e.g. `.` or `::`. Completion happens after it. The file is not changed"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Only return items whose name contains this text (case insensitive)"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": format!("The most items to return. Defaults to {DEFAULT_LIMIT}, at most {MAX_LIMIT}")
+                    }
+                },
+                "required": ["file", "line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let argument = |name: &str| {
+        request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get(name))
+            .and_then(|v| v.as_str())
+    };
+    let limit = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("limit"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_LIMIT)
+        .clamp(1, MAX_LIMIT);
+    let position = get_position(&project, relative_file, request).await?;
+
+    let items = project
+        .lsp
+        .completion(
+            relative_file,
+            position,
+            argument("insert"),
+            argument("filter"),
+            limit as usize,
+        )
+        .await
+        .map_err(|e| error_response(&e.to_string()))?;
+    if items.is_empty() {
+        return Err(error_response("No completions at this position"));
+    }
+
+    let text = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| format_item(i + 1, item))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
+}
+
+fn format_item(rank: usize, item: &CompletionItem) -> String {
+    let mut label = item.label.clone();
+    if let Some(details) = &item.label_details {
+        label.push_str(details.detail.as_deref().unwrap_or_default());
+    }
+    let mut line = format!("{rank}. `{label}`");
+    if let Some(kind) = item.kind {
+        line.push_str(&format!(" ({})", format!("{kind:?}").to_lowercase()));
+    }
+    if let Some(detail) = &item.detail {
+        line.push_str(&format!(" `{detail}`"));
+    }
+    if item.deprecated == Some(true) {
+        line.push_str(" deprecated");
+    }
+    if let Some(documentation) = &item.documentation {
+        let docs = documentation_text(documentation)
+            .split("\n\n")
+            .next()
+            .unwrap_or_default()
+            .replace('\n', " ");
+        let docs = docs.trim();
+        if !docs.is_empty() {
+            let mut shortened: String = docs.chars().take(MAX_DOCS_CHARS).collect();
+            if shortened.len() < docs.len() {
+                shortened.push('…');
+            }
+            line.push_str(&format!("\n   {shortened}"));
+        }
+    }
+    for edit in item.additional_text_edits.iter().flatten() {
+        let added = edit.new_text.trim();
+        if !added.is_empty() {
+            line.push_str(&format!("\n   Adds: `{added}`"));
+        }
+    }
+    line
+}
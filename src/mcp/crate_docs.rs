@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use crate::context::{Context, ProjectContext};
+use crate::{
+    context::{Context, ProjectContext},
+    docs::{CrateDocsMode, CrateDocsQuery, utils::ItemKind},
+};
 use anyhow::Result;
 use mcp_core::{
     tools::ToolHandlerFn,
@@ -18,8 +21,8 @@ pub struct CrateDocs;
 impl CrateDocs {
     pub fn tool() -> Tool {
         Tool {
-            name: "symbol_docs".to_string(),
-            description: Some("Get the documentation for a cargo dependency".to_string()),
+            name: "crate_docs".to_string(),
+            description: Some("Get the documentation for a cargo dependency. Without a symbol, returns an overview of the crate (or of a module) by default. Large responses are paginated: if the response ends with a `next_cursor`, call again with that cursor to get the next page.".to_string()),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -29,7 +32,28 @@ impl CrateDocs {
                     },
                     "symbol": {
                         "type": "string",
-                        "description": "The optional name of a symbol in the documentation. If not provided, the main readme for the dependency will be returned."
+                        "description": "The optional path of a symbol in the documentation, e.g. `tokio::sync::Mutex`, `sync::Mutex`, `Mutex` or a method like `Mutex::lock`. If not provided, the main readme for the dependency will be returned."
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["overview", "full"],
+                        "description": "`overview` returns the crate-level docs and a listing of modules and items. `full` returns the complete docs of every item. Default is `overview`."
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Optional kind of items to restrict the docs (or the symbol lookup) to, e.g. `trait` to list all traits of a crate. One of `mod`, `fn`, `macro`, `derive`, `attr`, `struct`, `enum`, `union`, `trait`, `traitalias`, `type`, `constant`, `static`, `primitive`, `keyword`, `foreigntype`."
+                    },
+                    "module": {
+                        "type": "string",
+                        "description": "Optional module to restrict the docs to, e.g. `tokio::sync`"
+                    },
+                    "cursor": {
+                        "type": "number",
+                        "description": "The `next_cursor` returned by a previous call, to get the next page"
+                    },
+                    "max_bytes": {
+                        "type": "number",
+                        "description": "Optional maximum size of the response in bytes. Default is 40000."
                     },
                     "file": {
                         "type": "string",
@@ -98,28 +122,86 @@ async fn handle_request(
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    if let Some(symbol) = symbol {
-        let docs = project
+    let kind = match request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("kind"))
+        .and_then(|v| v.as_str())
+    {
+        Some(kind) => Some(ItemKind::from_filter(kind).ok_or_else(|| {
+            error_response(&format!(
+                "Unknown kind `{kind}`, expected one of: {}",
+                ItemKind::ALL
+                    .iter()
+                    .map(|kind| kind.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?),
+        None => None,
+    };
+
+    let cursor = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("cursor"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize;
+    let max_bytes = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("max_bytes"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize);
+
+    let page = if let Some(symbol) = symbol {
+        project
             .docs
-            .crate_symbol_docs(&dependency, &symbol)
+            .crate_symbol_docs(&dependency, &symbol, kind, cursor, max_bytes)
             .await
-            .map_err(|e| error_response(&format!("{e:?}")))?;
-        let docs = docs.into_iter().map(|(k, v)| format!("{k}: {v}")).collect();
-        Ok(CallToolResponse {
-            content: vec![ToolResponseContent::Text { text: docs }],
-            is_error: None,
-            meta: None,
-        })
+            .map_err(|e| error_response(&format!("{e:?}")))?
     } else {
-        let docs = project
+        let query = CrateDocsQuery {
+            mode: match request
+                .arguments
+                .as_ref()
+                .and_then(|args| args.get("mode"))
+                .and_then(|v| v.as_str())
+            {
+                None | Some("overview") => CrateDocsMode::Overview,
+                Some("full") => CrateDocsMode::Full,
+                Some(other) => {
+                    return Err(error_response(&format!(
+                        "Unknown mode `{other}`, expected `overview` or `full`"
+                    )));
+                }
+            },
+            module: request
+                .arguments
+                .as_ref()
+                .and_then(|args| args.get("module"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            kind,
+            cursor,
+            max_bytes,
+        };
+        project
             .docs
-            .crate_docs(&dependency)
+            .crate_docs(&dependency, &query)
             .await
-            .map_err(|e| error_response(&format!("{e:?}")))?;
-        Ok(CallToolResponse {
-            content: vec![ToolResponseContent::Text { text: docs }],
-            is_error: None,
-            meta: None,
-        })
+            .map_err(|e| error_response(&format!("{e:?}")))?
+    };
+    let mut text = page.content;
+    if let Some(next_cursor) = page.next_cursor {
+        text.push_str(&format!(
+            "\n---\nShowing sections {cursor}..{next_cursor} of {}. next_cursor: {next_cursor}\n",
+            page.total_sections
+        ));
     }
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
 }
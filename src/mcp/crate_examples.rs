@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    docs::utils::ItemKind,
+};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request},
+};
+
+pub struct CrateExamples;
+
+impl CrateExamples {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "crate_examples".to_string(),
+            description: Some("Get usage examples for a cargo dependency: the doc-test code blocks of a symbol (or of the crate root) and the files from the `examples/` directory of the dependency sources, ranked by how often they mention the symbol. Large responses are paginated: if the response ends with a `next_cursor`, call again with that cursor to get the next page.".to_string()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "dependency": {
+                        "type": "string",
+                        "description": "The name of the cargo dependency to get examples for"
+                    },
+                    "symbol": {
+                        "type": "string",
+                        "description": "The optional path of a symbol, e.g. `tokio::sync::Mutex`, `Mutex` or `Mutex::lock`. If not provided, the examples of the crate root are returned."
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Optional kind of the symbol, e.g. `struct` or `fn`"
+                    },
+                    "cursor": {
+                        "type": "number",
+                        "description": "The `next_cursor` returned by a previous call, to get the next page"
+                    },
+                    "max_bytes": {
+                        "type": "number",
+                        "description": "Optional maximum size of the response in bytes. Default is 40000."
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the `Cargo.toml` file of the project"
+                    }
+                },
+                "required": ["dependency", "file"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    _relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let dependency = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("dependency"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| error_response("Dependency is required"))?;
+
+    let symbol = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("symbol"))
+        .and_then(|v| v.as_str());
+
+    let kind = match request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("kind"))
+        .and_then(|v| v.as_str())
+    {
+        Some(kind) => Some(ItemKind::from_filter(kind).ok_or_else(|| {
+            error_response(&format!(
+                "Unknown kind `{kind}`, expected one of: {}",
+                ItemKind::ALL
+                    .iter()
+                    .map(|kind| kind.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?),
+        None => None,
+    };
+
+    let cursor = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("cursor"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize;
+    let max_bytes = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("max_bytes"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize);
+
+    let page = project
+        .docs
+        .crate_examples(dependency, symbol, kind, cursor, max_bytes)
+        .await
+        .map_err(|e| error_response(&format!("{e:?}")))?;
+    let mut text = page.content;
+    if let Some(next_cursor) = page.next_cursor {
+        text.push_str(&format!(
+            "\n---\nShowing sections {cursor}..{next_cursor} of {}. next_cursor: {next_cursor}\n",
+            page.total_sections
+        ));
+    }
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::{crate_graph_adjacency, crate_graph_json},
+};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, truncate_text},
+};
+
+/// The full graph includes every dependency and the standard library
+const MAX_OUTPUT_LENGTH: usize = 60_000;
+
+pub struct CrateGraph;
+
+impl CrateGraph {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "crate_graph".to_string(),
+            description: Some(
+                "Get the crate graph rust-analyzer works with, including build script and proc-macro crates: as GraphViz DOT and as a JSON object mapping each crate to the crates it depends on. By default only the workspace crates are included.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the `Cargo.toml` file or any other file of the project"
+                    },
+                    "dependencies": {
+                        "type": "boolean",
+                        "description": "Include all dependencies and the standard library, not only the workspace crates (default false)"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["dot", "json", "both"],
+                        "description": "Return the DOT graph, the JSON adjacency list or both (default both)"
+                    }
+                },
+                "required": ["file"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    _relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let argument = |name: &str| request.arguments.as_ref().and_then(|args| args.get(name));
+    let dependencies = argument("dependencies")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let format = argument("format").and_then(|v| v.as_str()).unwrap_or("both");
+    if !["dot", "json", "both"].contains(&format) {
+        return Err(error_response(
+            "format has to be \"dot\", \"json\" or \"both\"",
+        ));
+    }
+
+    let dot = project
+        .lsp
+        .crate_graph(dependencies)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?;
+
+    let mut sections = Vec::new();
+    if format != "dot" {
+        let graph = crate_graph_adjacency(&dot);
+        let (json, included) = crate_graph_json(&graph, MAX_OUTPUT_LENGTH);
+        let mut section = format!(
+            "{} crates, each with the crates it depends on:\n```json\n{json}\n```",
+            graph.len()
+        );
+        if included < graph.len() {
+            section.push_str(&format!(
+                "\n{} crates were left out to keep the output short. Use the DOT format or leave out the dependencies to see all of them.",
+                graph.len() - included
+            ));
+        }
+        sections.push(section);
+    }
+    if format != "json" {
+        sections.push(format!(
+            "```dot\n{}\n```",
+            truncate_text(dot.trim_end(), MAX_OUTPUT_LENGTH)
+        ));
+    }
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: sections.join("\n\n"),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
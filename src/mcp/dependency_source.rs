@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    docs::{examples::mention_name, utils::ItemKind},
+};
+use anyhow::Result;
+use lsp_types::SymbolInformation;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request},
+};
+
+pub struct DependencySource;
+
+impl DependencySource {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "dependency_source".to_string(),
+            description: Some("Read the source code of a cargo dependency, resolved via cargo metadata (registry, git checkout or path). Without `path` or `symbol` the files of the dependency are listed. With `path` a file is returned, with `symbol` the source of that item, e.g. `serde_json::from_str`. Only files within the dependency sources can be read.".to_string()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "dependency": {
+                        "type": "string",
+                        "description": "The name of the cargo dependency"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Optional path of a file relative to the root of the dependency, e.g. `src/de.rs`"
+                    },
+                    "symbol": {
+                        "type": "string",
+                        "description": "Optional path of an item to return the source of, e.g. `serde_json::from_str`, `Mutex` or `Mutex::lock`"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Optional kind of the symbol, e.g. `struct` or `fn`"
+                    },
+                    "start_line": {
+                        "type": "number",
+                        "description": "Optional first line (1-based) to return of the file given by `path`"
+                    },
+                    "end_line": {
+                        "type": "number",
+                        "description": "Optional last line (1-based, inclusive) to return of the file given by `path`"
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the `Cargo.toml` file of the project"
+                    }
+                },
+                "required": ["dependency", "file"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    _relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let argument = |name: &str| {
+        request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get(name))
+            .and_then(|v| v.as_str())
+    };
+    let line_argument = |name: &str| {
+        request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get(name))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+    };
+    let dependency =
+        argument("dependency").ok_or_else(|| error_response("Dependency is required"))?;
+
+    let kind = match argument("kind") {
+        Some(kind) => Some(ItemKind::from_filter(kind).ok_or_else(|| {
+            error_response(&format!(
+                "Unknown kind `{kind}`, expected one of: {}",
+                ItemKind::ALL
+                    .iter()
+                    .map(|kind| kind.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?),
+        None => None,
+    };
+
+    let source = project
+        .docs
+        .dependency_source(dependency)
+        .await
+        .map_err(|e| error_response(&format!("{e:?}")))?;
+    let header = format!(
+        "# {} {} ({}: {})\n",
+        source.name,
+        source.version,
+        source.origin(),
+        source.root.display()
+    );
+
+    let text = if let Some(symbol) = argument("symbol") {
+        let mut sections = Vec::new();
+        for (path, location) in project.docs.source_locations(dependency, symbol, kind).await {
+            let file = source.src_root.join(&location.file);
+            let Ok(content) = source.read(
+                &file,
+                Some(location.start_line as usize),
+                Some(location.end_line as usize),
+            ) else {
+                continue;
+            };
+            sections.push(format!(
+                "## {path}\n{}:{}-{}\n```rust\n{content}\n```",
+                display_path(&source, &file),
+                location.start_line,
+                location.end_line
+            ));
+        }
+
+        // Items without a docs page of their own, like methods, or crates
+        // without generated docs are looked up with rust-analyzer
+        if sections.is_empty() {
+            let symbols = project
+                .lsp
+                .workspace_symbols(mention_name(symbol))
+                .await
+                .map_err(|e| error_response(&e.to_string()))?;
+            for symbol_information in best_symbol_matches(symbol, symbols) {
+                let Ok(file) = symbol_information.location.uri.to_file_path() else {
+                    continue;
+                };
+                if !source.contains(&file) {
+                    continue;
+                }
+                let range = symbol_information.location.range;
+                let start_line = range.start.line as usize + 1;
+                let end_line = range.end.line as usize + 1;
+                let Ok(content) = source.read(&file, Some(start_line), Some(end_line)) else {
+                    continue;
+                };
+                let name = match &symbol_information.container_name {
+                    Some(container) => format!("{container}::{}", symbol_information.name),
+                    None => symbol_information.name.clone(),
+                };
+                sections.push(format!(
+                    "## {name}\n{}:{start_line}-{end_line}\n```rust\n{content}\n```",
+                    display_path(&source, &file)
+                ));
+            }
+        }
+
+        if sections.is_empty() {
+            return Err(error_response(&format!(
+                "No source found for `{symbol}` in `{}`",
+                source.name
+            )));
+        }
+        format!("{header}\n{}", sections.join("\n\n"))
+    } else if let Some(path) = argument("path") {
+        let content = source
+            .read(path, line_argument("start_line"), line_argument("end_line"))
+            .map_err(|e| error_response(&format!("{e:?}")))?;
+        format!("{header}\n## {path}\n```rust\n{content}\n```")
+    } else {
+        let files = source
+            .files()
+            .map_err(|e| error_response(&format!("{e:?}")))?;
+        let files = files
+            .iter()
+            .map(|file| format!("- {}", file.display()))
+            .collect::<Vec<_>>();
+        format!("{header}\n{} files:\n{}", files.len(), files.join("\n"))
+    };
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
+}
+
+/// The symbols whose container matches the parent segments of the query, e.g.
+/// `Mutex` for `tokio::sync::Mutex::lock`. Falls back to all symbols.
+fn best_symbol_matches(query: &str, symbols: Vec<SymbolInformation>) -> Vec<SymbolInformation> {
+    let name = mention_name(query);
+    let mut symbols = symbols
+        .into_iter()
+        .filter(|symbol| symbol.name == name)
+        .collect::<Vec<_>>();
+    let parent = query
+        .rsplit("::")
+        .nth(1)
+        .filter(|parent| !parent.is_empty());
+    if let Some(parent) = parent
+        && symbols
+            .iter()
+            .any(|symbol| symbol.container_name.as_deref() == Some(parent))
+    {
+        symbols.retain(|symbol| symbol.container_name.as_deref() == Some(parent));
+    }
+    symbols
+}
+
+fn display_path(source: &crate::docs::source::DependencySource, file: &std::path::Path) -> String {
+    let root = dunce::canonicalize(&source.root).unwrap_or_else(|_| source.root.clone());
+    file.strip_prefix(&root)
+        .or_else(|_| file.strip_prefix(&source.root))
+        .unwrap_or(file)
+        .display()
+        .to_string()
+}
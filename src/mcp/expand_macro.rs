@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use crate::context::{Context, ProjectContext};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, get_position, truncate_text},
+};
+
+/// Expansions are cut off after this many bytes
+const MAX_EXPANSION_LENGTH: usize = 60_000;
+
+pub struct ExpandMacro;
+
+impl ExpandMacro {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "expand_macro".to_string(),
+            description: Some(
+                "Show the code a macro generates: a `macro_rules!` call, a `#[derive(...)]` or another attribute macro, or a proc macro call like `#[tokio::main]`. The expansion is recursive (macros inside it are expanded too) and pretty-printed.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the macro call"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line of the macro call or attribute (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column of the macro name (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: the macro name as it appears on the line, e.g. `vec!` or `Serialize` in a derive"
+                    }
+                },
+                "required": ["file", "line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let position = get_position(&project, relative_file, request).await?;
+    let expanded = project
+        .lsp
+        .expand_macro(relative_file, position)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?
+        .ok_or_else(|| {
+            error_response("No macro call found at this position. Point at the name of the macro or derive")
+        })?;
+
+    let expansion = truncate_text(expanded.expansion.trim_end(), MAX_EXPANSION_LENGTH);
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: format!(
+                "Expansion of `{}`:\n```rust\n{expansion}\n```",
+                expanded.name
+            ),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
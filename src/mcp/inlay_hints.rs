@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::render_inlay_hints,
+};
+use anyhow::Result;
+use lsp_types::{Position, Range};
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request},
+};
+
+/// Lines shown when no `end_line` is given
+const DEFAULT_LINES: u64 = 50;
+/// The most lines returned at once
+const MAX_LINES: u64 = 500;
+
+pub struct InlayHints;
+
+impl InlayHints {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "inlay_hints".to_string(),
+            description: Some(
+                "Show the source of a line range with the inferred types and parameter names inserted inline, like an editor shows them. Hints are wrapped in «»: `«: Type»` after `let` bindings and closure parameters, `«name:»` before call arguments and the type of each step of a method chain at the end of its line. Much cheaper than looking up symbols one at a time.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file"
+                    },
+                    "start_line": {
+                        "type": "number",
+                        "description": "The first line to show (1 based)"
+                    },
+                    "end_line": {
+                        "type": "number",
+                        "description": format!("The last line to show (1 based, inclusive). Defaults to {DEFAULT_LINES} lines, at most {MAX_LINES} lines are shown")
+                    }
+                },
+                "required": ["file", "start_line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let line_argument = |name: &str| {
+        request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get(name))
+            .and_then(|v| v.as_u64())
+    };
+    let start_line = line_argument("start_line")
+        .ok_or_else(|| error_response("Start line is required"))?
+        .max(1);
+    let end_line = line_argument("end_line")
+        .unwrap_or(start_line + DEFAULT_LINES - 1)
+        .clamp(start_line, start_line + MAX_LINES - 1);
+    // LSP lines are 0-based
+    let (start, end) = ((start_line - 1) as u32, (end_line - 1) as u32);
+
+    let content = project
+        .lsp
+        .document_text(relative_file)
+        .await
+        .map_err(|e| error_response(&format!("{e:#}")))?;
+    let hints = project
+        .lsp
+        .inlay_hints(
+            relative_file,
+            Range::new(Position::new(start, 0), Position::new(end + 1, 0)),
+        )
+        .await
+        .map_err(|e| error_response(&e.to_string()))?;
+
+    let text = render_inlay_hints(&content, start, end, &hints);
+    if text.is_empty() {
+        return Err(error_response(&format!(
+            "{relative_file} has only {} lines",
+            content.lines().count()
+        )));
+    }
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
+}
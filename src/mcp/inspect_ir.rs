@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::{position_to_byte_offset, syntax_subtree},
+};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, get_position, truncate_text},
+};
+
+/// MIR of larger functions easily runs into hundreds of kilobytes
+const MAX_OUTPUT_LENGTH: usize = 40_000;
+
+pub struct InspectIr;
+
+impl InspectIr {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "inspect_ir".to_string(),
+            description: Some(
+                "Show rust-analyzer's internal view of code, for borrow checker and type inference questions hover can't answer: the HIR (desugared body with inferred types) or MIR (control flow, borrows, moves and drops) of the function at a position, or the syntax tree of the item at a position. Large outputs are truncated.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "A line inside the function or item (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "A column on the line (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: text on the line, e.g. the function name"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["hir", "mir", "syntax"],
+                        "description": "hir: the function's HIR; mir: the function's MIR; syntax: the syntax tree of the top-level item"
+                    }
+                },
+                "required": ["file", "line", "kind"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let kind = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("kind"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| error_response("kind is required: \"hir\", \"mir\" or \"syntax\""))?;
+    let position = get_position(&project, relative_file, request).await?;
+
+    let output = match kind {
+        "hir" => project.lsp.view_hir(relative_file, position).await,
+        "mir" => project.lsp.view_mir(relative_file, position).await,
+        "syntax" => {
+            let content = project
+                .lsp
+                .document_text(relative_file)
+                .await
+                .map_err(|e| error_response(&format!("{e:#}")))?;
+            // rust-analyzer counts offsets in the text with normalized line endings
+            let offset = position_to_byte_offset(&content.replace("\r\n", "\n"), position);
+            project
+                .lsp
+                .view_syntax_tree(relative_file)
+                .await
+                .map(|tree| syntax_subtree(&tree, offset).to_string())
+        }
+        _ => {
+            return Err(error_response(
+                "kind has to be \"hir\", \"mir\" or \"syntax\"",
+            ));
+        }
+    }
+    .map_err(|e| error_response(&e.to_string()))?;
+    if output.trim().is_empty() {
+        return Err(error_response(&format!(
+            "rust-analyzer returned no {kind} for this position. HIR and MIR need a position inside a function"
+        )));
+    }
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: format!(
+                "```\n{}\n```",
+                truncate_text(output.trim_end(), MAX_OUTPUT_LENGTH)
+            ),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
@@ -1,10 +1,25 @@
 mod cargo_check;
 mod cargo_test;
+mod code_actions;
+mod complete_at;
 mod crate_docs;
+mod crate_graph;
+mod crate_examples;
+mod dependency_source;
+mod expand_macro;
+mod inlay_hints;
+mod inspect_ir;
+mod related_tests;
+mod rename_symbol;
+mod runnables;
+mod signature_help;
+mod ssr;
 mod symbol_docs;
 mod symbol_impl;
 mod symbol_references;
 mod symbol_resolve;
+mod sync_document;
+mod type_layout;
 mod utils;
 
 use std::path::PathBuf;
@@ -59,6 +74,66 @@ pub async fn run_server(context: Context) -> Result<()> {
             crate_docs::CrateDocs::tool(),
             crate_docs::CrateDocs::call(context.clone()),
         )
+        .register_tool(
+            crate_examples::CrateExamples::tool(),
+            crate_examples::CrateExamples::call(context.clone()),
+        )
+        .register_tool(
+            dependency_source::DependencySource::tool(),
+            dependency_source::DependencySource::call(context.clone()),
+        )
+        .register_tool(
+            inlay_hints::InlayHints::tool(),
+            inlay_hints::InlayHints::call(context.clone()),
+        )
+        .register_tool(
+            signature_help::SignatureHelp::tool(),
+            signature_help::SignatureHelp::call(context.clone()),
+        )
+        .register_tool(
+            complete_at::CompleteAt::tool(),
+            complete_at::CompleteAt::call(context.clone()),
+        )
+        .register_tool(
+            rename_symbol::RenameSymbol::tool(),
+            rename_symbol::RenameSymbol::call(context.clone()),
+        )
+        .register_tool(
+            code_actions::CodeActions::tool(),
+            code_actions::CodeActions::call(context.clone()),
+        )
+        .register_tool(
+            ssr::StructuralSearchReplace::tool(),
+            ssr::StructuralSearchReplace::call(context.clone()),
+        )
+        .register_tool(
+            expand_macro::ExpandMacro::tool(),
+            expand_macro::ExpandMacro::call(context.clone()),
+        )
+        .register_tool(
+            runnables::Runnables::tool(),
+            runnables::Runnables::call(context.clone()),
+        )
+        .register_tool(
+            related_tests::RelatedTests::tool(),
+            related_tests::RelatedTests::call(context.clone()),
+        )
+        .register_tool(
+            inspect_ir::InspectIr::tool(),
+            inspect_ir::InspectIr::call(context.clone()),
+        )
+        .register_tool(
+            type_layout::TypeLayout::tool(),
+            type_layout::TypeLayout::call(context.clone()),
+        )
+        .register_tool(
+            crate_graph::CrateGraph::tool(),
+            crate_graph::CrateGraph::call(context.clone()),
+        )
+        .register_tool(
+            sync_document::SyncDocument::tool(),
+            sync_document::SyncDocument::call(context.clone()),
+        )
         .register_tool(
             cargo_check::CargoCheck::tool(),
             cargo_check::CargoCheck::call(context.clone()),
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::context::{Context, ProjectContext};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, get_position},
+};
+
+/// Longer tests are cut off
+const MAX_SOURCE_LINES: usize = 60;
+
+pub struct RelatedTests;
+
+impl RelatedTests {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "related_tests".to_string(),
+            description: Some(
+                "Find the tests that exercise a function, with their source and the exact command to run each. After changing a function, run just these with cargo_test (pass the test's file and its label as runnable) instead of the whole suite.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the function"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line of the function (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column of the function name (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: the function name as it appears on the line"
+                    }
+                },
+                "required": ["file", "line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let position = get_position(&project, relative_file, request).await?;
+    let tests = project
+        .lsp
+        .related_tests(relative_file, position)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?;
+    if tests.is_empty() {
+        return Err(error_response(
+            "No tests found that call this function. The position has to be on a function name",
+        ));
+    }
+
+    let mut sections = Vec::new();
+    for test in &tests {
+        let mut section = format!("## `{}`\n", test.label);
+        if let Some(location) = &test.location
+            && let Ok(path) = location.target_uri.to_file_path()
+        {
+            let start = location.target_range.start.line as usize;
+            let end = location.target_range.end.line as usize;
+            let relative = project.project.relative_path(&path).ok();
+            section.push_str(&format!(
+                "{}:{}\n",
+                relative.as_deref().unwrap_or(&path.to_string_lossy()),
+                start + 1
+            ));
+            let source = match &relative {
+                Some(relative) => project.lsp.document_text(relative).await.ok(),
+                None => None,
+            };
+            if let Some(source) = source {
+                let lines: Vec<&str> = source
+                    .lines()
+                    .skip(start)
+                    .take((end + 1 - start).min(MAX_SOURCE_LINES))
+                    .collect();
+                let mut code = lines.join("\n");
+                if end + 1 - start > MAX_SOURCE_LINES {
+                    code.push_str("\n// ...");
+                }
+                section.push_str(&format!("```rust\n{code}\n```\n"));
+            }
+        }
+        section.push_str(&format!("Run: `{}`", test.command_line()));
+        sections.push(section);
+    }
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: format!(
+                "{} tests exercise this function:\n\n{}",
+                tests.len(),
+                sections.join("\n\n")
+            ),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
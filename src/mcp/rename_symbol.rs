@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::utf16_to_byte_offset,
+};
+use anyhow::Result;
+use lsp_types::PrepareRenameResponse;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{edit_response, error_response, get_info_from_request, get_position},
+};
+
+pub struct RenameSymbol;
+
+impl RenameSymbol {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "rename_symbol".to_string(),
+            description: Some(
+                "Rename a symbol (function, type, field, variable, module, ...) everywhere it is used, the way an editor's rename does. Renaming a module also renames its file. By default only a unified diff of the changes is returned; set apply to write them.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the symbol"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line of the symbol (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column of the symbol (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: the symbol's name as it appears on the line"
+                    },
+                    "new_name": {
+                        "type": "string",
+                        "description": "The new name of the symbol"
+                    },
+                    "apply": {
+                        "type": "boolean",
+                        "description": "Write the changes to disk instead of only previewing them (default false)"
+                    }
+                },
+                "required": ["file", "line", "new_name"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let arguments = request.arguments.as_ref();
+    let new_name = arguments
+        .and_then(|args| args.get("new_name"))
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| error_response("new_name is required"))?;
+    let apply = arguments
+        .and_then(|args| args.get("apply"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let position = get_position(&project, relative_file, request).await?;
+
+    // Fails early with rust-analyzer's reason if the symbol can't be renamed
+    let prepared = project
+        .lsp
+        .prepare_rename(relative_file, position)
+        .await
+        .map_err(|e| error_response(&format!("Cannot rename here: {e}")))?
+        .ok_or_else(|| error_response("No symbol to rename at this position"))?;
+    let old_name = match prepared {
+        PrepareRenameResponse::RangeWithPlaceholder { placeholder, .. } => Some(placeholder),
+        PrepareRenameResponse::Range(range) => project
+            .lsp
+            .document_text(relative_file)
+            .await
+            .ok()
+            .and_then(|content| {
+                let line = content.lines().nth(range.start.line as usize)?;
+                let start = utf16_to_byte_offset(line, range.start.character);
+                let end = utf16_to_byte_offset(line, range.end.character);
+                line.get(start..end).map(str::to_string)
+            }),
+        PrepareRenameResponse::DefaultBehavior { .. } => None,
+    };
+
+    let edit = project
+        .lsp
+        .rename(relative_file, position, new_name)
+        .await
+        .map_err(|e| error_response(&format!("Rename failed: {e}")))?
+        .ok_or_else(|| error_response("rust-analyzer returned no changes for this rename"))?;
+    let renaming = match old_name {
+        Some(old_name) => format!("renaming `{old_name}` to `{new_name}`"),
+        None => format!("renaming to `{new_name}`"),
+    };
+    edit_response(&project, &edit, apply, &renaming).await
+}
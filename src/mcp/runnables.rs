@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::context::{Context, ProjectContext};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request},
+};
+
+pub struct Runnables;
+
+impl Runnables {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "runnables".to_string(),
+            description: Some(
+                "List the tests, test modules, benches, binaries and doctests in a file with the exact cargo command for each, including the package and the `--exact` test name. Run one of them with cargo_test by passing its label as runnable.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file"
+                    }
+                },
+                "required": ["file"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    _request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let runnables = project
+        .lsp
+        .runnables(relative_file, None)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?;
+    if runnables.is_empty() {
+        return Err(error_response(&format!(
+            "No tests, benches or binaries found in {relative_file}"
+        )));
+    }
+
+    let mut text = format!("Runnables in {relative_file}:\n");
+    for runnable in &runnables {
+        text.push_str(&format!("- `{}`", runnable.label));
+        if let Some(location) = &runnable.location {
+            text.push_str(&format!(
+                " (line {})",
+                location.target_selection_range.start.line + 1
+            ));
+        }
+        text.push_str(&format!("\n  {}\n", runnable.command_line()));
+    }
+    text.push_str("\nRun one with cargo_test, passing this file and the label as runnable");
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
+}
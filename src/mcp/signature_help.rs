@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::format_signature_help,
+};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, get_position},
+};
+
+pub struct SignatureHelp;
+
+impl SignatureHelp {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "signature_help".to_string(),
+            description: Some(
+                "Get the signatures of the function or method called at a position inside its argument list: every candidate (e.g. trait methods), which parameter the position is at and the parameter docs. Use it to get the argument order of a call right.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the call"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line of the call (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column inside the argument list (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: text on the line inside the argument list, e.g. the argument being edited. Its start is used as the position"
+                    }
+                },
+                "required": ["file", "line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let position = get_position(&project, relative_file, request).await?;
+    let help = project
+        .lsp
+        .signature_help(relative_file, position)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?
+        .filter(|help| !help.signatures.is_empty())
+        .ok_or_else(|| error_response("No call found at this position. The position has to be inside the parentheses of a call"))?;
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: format_signature_help(&help),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
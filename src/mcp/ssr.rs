@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::retain_files,
+};
+use anyhow::Result;
+use lsp_types::Position;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{edit_response, error_response, get_info_from_request, get_position},
+};
+
+pub struct StructuralSearchReplace;
+
+impl StructuralSearchReplace {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "ssr".to_string(),
+            description: Some(
+                "Structural search and replace: rewrite code that matches a pattern semantically instead of textually, e.g. `foo($a, $b) ==>> bar($b, $a)` or `$s.to_string() ==>> String::from($s)`. Placeholders like `$a` match any expression, and paths match the same item however they are written or imported. Use it for mechanical API migrations. By default the changes are previewed as a diff; mode \"parse\" only checks the rule and mode \"apply\" writes the changes.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The rule: a search pattern, `==>>` and the replacement"
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to a file of the project. Paths in the rule are resolved as if written in this file"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line at which paths in the rule are resolved, e.g. inside a module with the right imports (1 based, default the start of the file)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column on the line (1 based, default 1)"
+                    },
+                    "files": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Absolute paths of the files to change. Without it, the whole workspace is changed"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["parse", "preview", "apply"],
+                        "description": "parse: only check the rule; preview: show the changes as a diff (default); apply: write the changes"
+                    }
+                },
+                "required": ["query", "file"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let argument = |name: &str| request.arguments.as_ref().and_then(|args| args.get(name));
+    let query = argument("query")
+        .and_then(|v| v.as_str())
+        .filter(|query| !query.trim().is_empty())
+        .ok_or_else(|| error_response("query is required"))?;
+    let mode = argument("mode").and_then(|v| v.as_str()).unwrap_or("preview");
+    if !["parse", "preview", "apply"].contains(&mode) {
+        return Err(error_response(
+            "mode has to be \"parse\", \"preview\" or \"apply\"",
+        ));
+    }
+    let files = match argument("files").and_then(|v| v.as_array()) {
+        Some(files) => {
+            let mut uris = HashSet::new();
+            for file in files {
+                let file = file
+                    .as_str()
+                    .ok_or_else(|| error_response("files has to be a list of paths"))?;
+                let relative = project
+                    .project
+                    .relative_path(file)
+                    .map_err(|e| error_response(&e))?;
+                let uri = project
+                    .project
+                    .file_uri(relative)
+                    .map_err(|e| error_response(&e.to_string()))?;
+                uris.insert(uri);
+            }
+            Some(uris)
+        }
+        None => None,
+    };
+    let position = if argument("column").is_some() {
+        get_position(&project, relative_file, request).await?
+    } else {
+        let line = argument("line").and_then(|v| v.as_u64()).unwrap_or(1);
+        Position::new(line.saturating_sub(1) as u32, 0)
+    };
+
+    let mut edit = project
+        .lsp
+        .ssr(relative_file, position, query, mode == "parse")
+        .await
+        .map_err(|e| error_response(&format!("The rule failed: {e}")))?;
+    if mode == "parse" {
+        return Ok(CallToolResponse {
+            content: vec![ToolResponseContent::Text {
+                text: format!("The rule `{query}` is valid"),
+            }],
+            is_error: None,
+            meta: None,
+        });
+    }
+    if let Some(files) = &files {
+        retain_files(&mut edit, |uri| files.contains(uri));
+    }
+    let scope = if files.is_some() {
+        "the given files"
+    } else {
+        "the workspace"
+    };
+    edit_response(
+        &project,
+        &edit,
+        mode == "apply",
+        &format!("`{query}` in {scope}"),
+    )
+    .await
+}
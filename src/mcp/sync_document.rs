@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use crate::context::{Context, ProjectContext};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request},
+};
+
+pub struct SyncDocument;
+
+impl SyncDocument {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "sync_document".to_string(),
+            description: Some(
+                "Send the current contents of an editor buffer, including unsaved changes. Until the document is closed, all other tools answer against these contents instead of the file on disk. Call it again whenever the buffer changes, and with `close` once the buffer is saved or discarded.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file of the buffer"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "The full contents of the buffer. Required unless `close` is set"
+                    },
+                    "close": {
+                        "type": "boolean",
+                        "description": "Close the document, so the file on disk is used again"
+                    }
+                },
+                "required": ["file"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let args = request.arguments.as_ref();
+    let close = args
+        .and_then(|args| args.get("close"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let content = args
+        .and_then(|args| args.get("content"))
+        .and_then(|v| v.as_str());
+
+    let mut text = if close {
+        let was_open = project
+            .lsp
+            .close_document(relative_file)
+            .await
+            .map_err(|e| error_response(&e.to_string()))?;
+        if was_open {
+            format!("Closed {relative_file}. Tools use the file on disk again.")
+        } else {
+            format!("{relative_file} was not open.")
+        }
+    } else {
+        let Some(content) = content else {
+            return Err(error_response("Content is required unless `close` is set"));
+        };
+        let version = project
+            .lsp
+            .update_document(relative_file, content.to_string())
+            .await
+            .map_err(|e| error_response(&e.to_string()))?;
+        format!("Synced {relative_file} (version {version}). Tools answer against this content until it is closed.")
+    };
+
+    let open_documents = project.lsp.open_documents().await;
+    if !open_documents.is_empty() {
+        text.push_str("\n\nOpen documents:");
+        for (uri, version) in open_documents {
+            let path = uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| project.project.relative_path(&path).ok())
+                .unwrap_or_else(|| uri.to_string());
+            text.push_str(&format!("\n- {path} (version {version})"));
+        }
+    }
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text { text }],
+        is_error: None,
+        meta: None,
+    })
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use crate::{
+    context::{Context, ProjectContext},
+    lsp::format_memory_layout,
+};
+use anyhow::Result;
+use mcp_core::{
+    tools::ToolHandlerFn,
+    types::{CallToolRequest, CallToolResponse, Tool, ToolResponseContent},
+};
+use serde_json::json;
+
+use super::{
+    McpNotification,
+    utils::{error_response, get_info_from_request, get_position},
+};
+
+const DEFAULT_DEPTH: u64 = 1;
+const MAX_DEPTH: u64 = 5;
+
+pub struct TypeLayout;
+
+impl TypeLayout {
+    pub fn tool() -> Tool {
+        Tool {
+            name: "type_layout".to_string(),
+            description: Some(
+                "Show the memory layout of a type as the compiler lays it out: its size and alignment, and a table of its fields sorted by offset with their sizes, alignment and the padding after each. Use it to tune hot structs for cache lines instead of writing size_of tests.".to_string(),
+            ),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the type"
+                    },
+                    "line": {
+                        "type": "number",
+                        "description": "The line of the type's definition or a use of it (1 based)"
+                    },
+                    "column": {
+                        "type": "number",
+                        "description": "The column of the type name (1 based)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Instead of a column: the type name as it appears on the line"
+                    },
+                    "depth": {
+                        "type": "number",
+                        "description": "How many levels of nested fields to show (default 1, max 5)"
+                    }
+                },
+                "required": ["file", "line"]
+            }),
+        }
+    }
+
+    pub fn call(context: Context) -> ToolHandlerFn {
+        Box::new(move |request: CallToolRequest| {
+            let clone = context.clone();
+            Box::pin(async move {
+                let (project, relative_file, absolute_file) =
+                    match get_info_from_request(&clone, &request).await {
+                        Ok(info) => info,
+                        Err(response) => return response,
+                    };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Request {
+                        content: request.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                let response = match handle_request(project, &relative_file, &request).await {
+                    Ok(response) => response,
+                    Err(response) => response,
+                };
+                if let Err(e) = clone
+                    .send_mcp_notification(McpNotification::Response {
+                        content: response.clone(),
+                        project: absolute_file.clone(),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send MCP notification: {}", e);
+                }
+                response
+            })
+        })
+    }
+}
+
+async fn handle_request(
+    project: Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let depth = request
+        .arguments
+        .as_ref()
+        .and_then(|args| args.get("depth"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_DEPTH)
+        .clamp(1, MAX_DEPTH);
+    let position = get_position(&project, relative_file, request).await?;
+    let layout = project
+        .lsp
+        .memory_layout(relative_file, position)
+        .await
+        .map_err(|e| error_response(&e.to_string()))?
+        .filter(|layout| !layout.nodes.is_empty())
+        .ok_or_else(|| {
+            error_response("No layout for this position. Point at the name of a sized, non-generic type")
+        })?;
+
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: format_memory_layout(&layout, depth as usize),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
@@ -2,8 +2,9 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::context::{Context, ProjectContext};
+use crate::lsp::position_in_line;
 use anyhow::Result;
-use lsp_types::Position;
+use lsp_types::{Position, WorkspaceEdit};
 use mcp_core::types::{CallToolRequest, CallToolResponse, ToolResponseContent};
 
 pub fn error_response(message: &str) -> CallToolResponse {
@@ -98,6 +99,87 @@ pub async fn find_symbol_position_in_file(
     Err(format!("Symbol {symbol} not found in file {relative_file}"))
 }
 
+/// The position given by the 1-based `line` and either the 1-based `column`
+/// or a `text` on that line. Resolved against the synced buffer if there is one.
+pub async fn get_position(
+    project: &Arc<ProjectContext>,
+    relative_file: &str,
+    request: &CallToolRequest,
+) -> Result<Position, CallToolResponse> {
+    let args = request.arguments.as_ref();
+    let line = args
+        .and_then(|args| args.get("line"))
+        .and_then(|v| v.as_u64())
+        .filter(|line| *line > 0)
+        .ok_or_else(|| error_response("Line is required and 1 based"))?;
+    let column = args
+        .and_then(|args| args.get("column"))
+        .and_then(|v| v.as_u64());
+    let text = args
+        .and_then(|args| args.get("text"))
+        .and_then(|v| v.as_str());
+    let content = project
+        .lsp
+        .document_text(relative_file)
+        .await
+        .map_err(|e| error_response(&format!("{e:#}")))?;
+    position_in_line(&content, (line - 1) as u32, column.map(|c| c as u32), text)
+        .map_err(|e| error_response(&e))
+}
+
+/// Previews `edit` as a diff, or writes it to disk with `apply`. `change`
+/// describes the edit, e.g. "renaming `a` to `b`".
+pub async fn edit_response(
+    project: &ProjectContext,
+    edit: &WorkspaceEdit,
+    apply: bool,
+    change: &str,
+) -> Result<CallToolResponse, CallToolResponse> {
+    let plan = project
+        .lsp
+        .apply_edit(edit, !apply)
+        .await
+        .map_err(|e| error_response(&format!("Failed to apply {change}: {e:#}")))?;
+    if plan.is_empty() {
+        return Err(error_response(&format!("There is nothing to change for {change}")));
+    }
+    let files = plan
+        .changed_files()
+        .iter()
+        .map(|file| format!("- {}", file.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let summary = if apply {
+        format!("Applied {change}. Changed files:\n{files}")
+    } else {
+        format!(
+            "Preview of {change}, nothing was written. Call again with apply set to true to write it. Files that would change:\n{files}"
+        )
+    };
+    Ok(CallToolResponse {
+        content: vec![ToolResponseContent::Text {
+            text: format!("{summary}\n\n```diff\n{}```", plan.diff()),
+        }],
+        is_error: None,
+        meta: None,
+    })
+}
+
+/// Cuts `text` to at most `max_bytes` at a line end if possible, noting
+/// how much was left out
+pub fn truncate_text(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').unwrap_or(end);
+    let omitted = text[end..].trim_start_matches('\n').lines().count();
+    format!("{}\n... ({omitted} more lines truncated)", &text[..end])
+}
+
 /// Returns the lines between start_line and end_line (inclusive) from the given file path
 /// Optionally includes prefix lines before start_line and suffix lines after end_line
 /// Line numbers are 0-based
@@ -1,7 +1,9 @@
 use anyhow::Result;
 use dunce;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use url::Url;
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +16,49 @@ pub enum TransportType {
 pub struct Project {
     pub root: PathBuf,
     pub ignore_crates: Vec<String>,
+    /// Build the dependency docs like docs.rs, see [`Project::docsrs`]
+    #[serde(default)]
+    pub docsrs: Option<bool>,
+    #[serde(default)]
+    pub rust_analyzer: RustAnalyzerConfig,
+}
+
+/// How rust-analyzer is started for a project
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RustAnalyzerConfig {
+    /// The rust-analyzer binary. Defaults to `rust-analyzer` from the PATH
+    pub binary: Option<PathBuf>,
+    /// Extra arguments for the binary
+    pub args: Vec<String>,
+    /// Extra environment variables for the process, e.g. `RA_LOG`
+    pub env: BTreeMap<String, String>,
+    /// Install rust-analyzer with `rustup component add` if it's missing
+    pub auto_install: bool,
+    /// Stop rust-analyzer while indexing is paused, instead of only holding back requests
+    pub stop_on_pause: bool,
+    /// How many requests are sent to rust-analyzer at once. Defaults to 8
+    pub max_concurrent_requests: Option<usize>,
+    /// Seconds after which a request is given up. Defaults to 60
+    pub request_timeout_secs: Option<u64>,
+    /// rust-analyzer settings like `{"cargo": {"features": "all"}}`. Sent as
+    /// `initializationOptions` and answered for `workspace/configuration`.
+    pub settings: Option<serde_json::Value>,
+}
+
+impl RustAnalyzerConfig {
+    pub fn binary(&self) -> &Path {
+        self.binary
+            .as_deref()
+            .unwrap_or_else(|| Path::new("rust-analyzer"))
+    }
+
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.max_concurrent_requests.unwrap_or(8).max(1)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs.unwrap_or(60))
+    }
 }
 
 impl Project {
@@ -43,6 +88,8 @@ impl Project {
         Ok(Self {
             root,
             ignore_crates: vec![],
+            docsrs: None,
+            rust_analyzer: RustAnalyzerConfig::default(),
         })
     }
 
@@ -50,6 +97,14 @@ impl Project {
         &self.ignore_crates
     }
 
+    /// Whether the docs are generated with `--cfg docsrs`, which rustdoc
+    /// needs to show feature gates. This needs `RUSTC_BOOTSTRAP=1`, as most
+    /// crates enable the unstable `doc(cfg)` feature under `docsrs`.
+    /// Defaults to true.
+    pub fn docsrs(&self) -> bool {
+        self.docsrs.unwrap_or(true)
+    }
+
     pub fn root(&self) -> &PathBuf {
         &self.root
     }
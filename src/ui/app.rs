@@ -10,7 +10,7 @@ use flume::Receiver;
 use crate::{
     context::{Context, ContextNotification},
     project::Project,
-    lsp::{LspNotification, IndexingProgress},
+    lsp::{LspNotification, IndexingProgress, ServerHealth},
 };
 
 #[derive(Clone, Debug)]
@@ -46,6 +46,7 @@ pub struct App {
     selected_event: Option<TimestampedEvent>,
     project_descriptions: Vec<ProjectDescription>,
     indexing_progress: HashMap<PathBuf, IndexingProgress>,
+    server_health: HashMap<PathBuf, ServerHealth>,
 }
 
 impl App {
@@ -64,6 +65,7 @@ impl App {
             selected_event: None,
             project_descriptions,
             indexing_progress: HashMap::new(),
+            server_health: HashMap::new(),
         }
     }
 
@@ -104,10 +106,14 @@ impl App {
                 continue;
             }
             
+            if let ContextNotification::Lsp(LspNotification::ServerHealth(health)) = &notification {
+                self.server_health.insert(health.project.clone(), health.clone());
+            }
+
             // Filter out high-volume LSP notifications but allow indexing notifications through
             if let ContextNotification::Lsp(lsp) = &notification {
                 // Let indexing notifications through to update the UI spinner
-                if matches!(lsp, LspNotification::Indexing { .. }) {
+                if matches!(lsp, LspNotification::Indexing { .. } | LspNotification::ServerHealth(_)) {
                     has_new_events = true;
                     tracing::debug!("Received LSP indexing notification: {:?}", notification);
                     let project_path = notification.notification_path();
@@ -247,6 +253,8 @@ impl App {
                         let project = Project {
                             root: path_buf.clone(),
                             ignore_crates: vec![],
+                            docsrs: None,
+                            rust_analyzer: Default::default(),
                         };
 
                         if let Err(e) = context.add_project(project).await {
@@ -378,9 +386,15 @@ impl App {
                             }
                         }
                         ui.add_space(10.0);
-                        if project.is_indexing_lsp {
+                        let is_paused = self
+                            .indexing_progress
+                            .get(&project.root)
+                            .is_some_and(|progress| progress.is_paused);
+                        if project.is_indexing_lsp || is_paused {
                             ui.horizontal(|ui| {
-                                ui.add(egui::Spinner::new());
+                                if !is_paused {
+                                    ui.add(egui::Spinner::new());
+                                }
                                 
                                 // Show detailed progress information if available
                                 if let Some(progress) = self.indexing_progress.get(&project.root) {
@@ -413,6 +427,19 @@ impl App {
                                 }
                             });
                         }
+                        if let Some(health) = self.server_health.get(&project.root) {
+                            ui.add_space(10.0);
+                            if !health.is_running {
+                                ui.add(egui::Spinner::new());
+                            }
+                            let label = ui.label(format!(
+                                "rust-analyzer restarts: {}",
+                                health.restart_count
+                            ));
+                            if let Some(error) = &health.last_error {
+                                label.on_hover_text(format!("Last error: {error}"));
+                            }
+                        }
                         ui.add_space(10.0);
                         if project.is_indexing_docs {
                             ui.add(egui::Spinner::new());
@@ -440,7 +467,8 @@ impl App {
                                                 if let ContextNotification::Lsp(lsp) = &event_tuple.1 {
                                                     if !matches!(lsp, 
                                                         crate::lsp::LspNotification::Indexing { .. } | 
-                                                        crate::lsp::LspNotification::IndexingProgress(_)
+                                                        crate::lsp::LspNotification::IndexingProgress(_) |
+                                                        crate::lsp::LspNotification::ServerHealth(_)
                                                     ) {
                                                         continue;
                                                     }